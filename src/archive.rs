@@ -0,0 +1,252 @@
+//! # Archive
+//!
+//! Support for the `done.txt` archive of the todo.txt format.
+//! Like `todo.sh archive`, `TodoFile::archive` moves the completed todo items out of the todo
+//! file and appends them to a done file, `done.txt` next to the todo file unless
+//! `TodoFile::done_path` says otherwise.
+//!
+//! ```rust,no_run
+//! use libdonow::{archive::ArchiveOptions, file::TodoFile};
+//!
+//! let mut file = TodoFile::new("todo.txt");
+//! let options = ArchiveOptions {
+//!     retention: Some(chrono::Days::new(365)),
+//!     dedup: true,
+//! };
+//! let moved = file.archive_with(&options).expect("Failed to archive todo.txt");
+//! println!("archived {} todo items", moved);
+//!
+//! // queries over both files
+//! let everything = file.with_done().expect("Failed to read done.txt");
+//! println!("{}", everything.get_project("work").len());
+//! ```
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{file::TodoFile, merge};
+
+/// The name of the done file when `TodoFile::done_path` isn't set.
+pub const DONE_FILE: &str = "done.txt";
+
+/// Options for `TodoFile::archive_with`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArchiveOptions {
+    /// How long completed todo items are kept in the done file.
+    /// Todo items completed longer ago than this, counting from `TodoFile::today`, are
+    /// dropped from the done file. Todo items without a completion date are always kept.
+    pub retention: Option<chrono::Days>,
+    /// Whether to skip todo items that are already in the done file with the same line.
+    pub dedup: bool,
+}
+
+impl TodoFile {
+    /// Returns the path of the done file.
+    /// This is the `done_path` field, or `done.txt` in the same directory as the todo file.
+    pub fn done_path(&self) -> PathBuf {
+        match &self.done_path {
+            Some(path) => path.clone(),
+            None => self.path.parent().unwrap_or(Path::new("")).join(DONE_FILE),
+        }
+    }
+
+    /// Reads the done file.
+    /// A done file that doesn't exist yet is read as an empty file. The done file is parsed with
+    /// the same mode as the todo file.
+    pub fn done(&self) -> io::Result<TodoFile> {
+        let path = self.done_path();
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut done = TodoFile::from_string("");
+        done.path = path;
+        done.content = content;
+        done.mode = self.mode;
+        done.today = self.today;
+        done.backup = self.backup;
+        done.loaded_hash = Some(merge::hash(&done.content));
+        done.load();
+
+        Ok(done)
+    }
+
+    /// Returns a `TodoFile` with the todo items of both the todo file and the done file, so
+    /// that any of the queries can run over both. The todo items of the todo file come first.
+    /// The result is meant for reading only. It has no path, so saving it fails instead of
+    /// writing both sets of todo items to the todo file.
+    pub fn with_done(&self) -> io::Result<TodoFile> {
+        let done = self.done()?;
+
+        let mut all = TodoFile::from_string("");
+        all.mode = self.mode;
+        all.today = self.today;
+        all.separator = self.separator;
+        all.todos = self.todos.iter().cloned().chain(done.todos).collect();
+
+        Ok(all)
+    }
+
+    /// Moves the completed todo items to the done file using the default `ArchiveOptions`.
+    /// See `archive_with`.
+    pub fn archive(&mut self) -> io::Result<usize> {
+        self.archive_with(&ArchiveOptions::default())
+    }
+
+    /// Moves the completed todo items to the end of the done file, like `todo.sh archive`.
+    /// Both files are saved: the done file first, so that a failure in between leaves a todo
+    /// item in both files rather than in neither. Returns the number of todo items that were
+    /// moved out of the todo file.
+    ///
+    /// Returns an error without writing anything if the todo file has no path, like one made
+    /// with `TodoFile::from_string`, or if it was modified on disk since it was read, see
+    /// `TodoFile::save`. The todo items stay in the todo file when either file can't be
+    /// saved.
+    pub fn archive_with(&mut self, options: &ArchiveOptions) -> io::Result<usize> {
+        if self.path.as_os_str().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the todo file has no path to archive from",
+            ));
+        }
+        if self.is_modified_on_disk()? {
+            return Err(io::Error::other(merge::ModifiedOnDisk {
+                path: self.path.clone(),
+            }));
+        }
+
+        let mut done = self.done()?;
+        let todos = self.todos.clone();
+        let positions = self.raw_positions();
+        let history = self.history.clone();
+        let content = self.content.clone();
+
+        let mut moved = Vec::new();
+        for i in (0..self.todos.len()).rev() {
+            if self.todos[i].completed {
+                moved.push(self.todos[i].clone());
+                self.remove(i);
+            }
+        }
+        let count = moved.len();
+
+        for todo in moved.into_iter().rev() {
+            let line = todo.to_string();
+            if options.dedup && done.todos.iter().any(|t| t.to_string() == line) {
+                continue;
+            }
+            done.add(todo);
+        }
+
+        if let Some(cutoff) = options
+            .retention
+            .and_then(|r| self.today().checked_sub_days(r))
+        {
+            for i in (0..done.todos.len()).rev() {
+                if done.todos[i].completion.is_some_and(|d| d < cutoff) {
+                    done.remove(i);
+                }
+            }
+        }
+
+        if let Err(e) = done.save().and_then(|_| self.save()) {
+            // the todo file wasn't written if its content is still the one it was read with
+            if self.content == content {
+                self.todos = todos;
+                for (r, p) in self.raw.iter_mut().zip(positions) {
+                    r.position = p;
+                }
+                self.history = history;
+            }
+            return Err(e);
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_archive() {
+    let dir = crate::file::TempDir::new("archive");
+    let path = dir.join("todo.txt");
+    std::fs::write(
+        &path,
+        "x 2024-09-01 2024-08-01 Pay rent\nCall mom\nx 2024-09-02 Water plants\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("done.txt"),
+        "x 2023-01-01 2022-12-01 Old\nx 2024-09-01 2024-08-01 Pay rent\n",
+    )
+    .unwrap();
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    t.today = chrono::NaiveDate::from_ymd_opt(2024, 9, 3);
+    let options = ArchiveOptions {
+        retention: Some(chrono::Days::new(365)),
+        dedup: true,
+    };
+    assert_eq!(t.archive_with(&options).unwrap(), 2);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "Call mom\n");
+    assert_eq!(
+        std::fs::read_to_string(dir.join("done.txt")).unwrap(),
+        "x 2024-09-01 2024-08-01 Pay rent\nx 2024-09-02 Water plants\n"
+    );
+
+    let mut all = t.with_done().unwrap();
+    assert_eq!(all.len(), 3);
+    assert_eq!(all.completed().len(), 2);
+    assert_eq!(all[0].title, "Call mom");
+    assert!(all.save().is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "Call mom\n");
+
+    let mut t = TodoFile::from_string("x 2024-09-01 2024-08-01 Pay rent\n");
+    t.done_path = Some(dir.join("elsewhere.txt"));
+    assert!(t.archive().is_err());
+    assert!(!dir.join("elsewhere.txt").exists());
+    assert_eq!(t.len(), 1);
+}
+
+#[test]
+fn test_archive_modified_on_disk() {
+    let dir = crate::file::TempDir::new("archive_modified_on_disk");
+    let path = dir.join("todo.txt");
+    let done = dir.join("done.txt");
+    std::fs::write(&path, "x 2024-09-01 Pay rent\n# bills\nCall mom\n").unwrap();
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    std::fs::write(
+        &path,
+        "x 2024-09-01 Pay rent\n# bills\nCall mom\nWater plants\n",
+    )
+    .unwrap();
+    let e = t.archive().unwrap_err();
+    assert!(merge::is_modified_on_disk(&e));
+    assert!(!done.exists());
+    assert_eq!(t.len(), 2);
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    t.done_path = Some(dir.join("missing").join("done.txt"));
+    assert!(t.archive().is_err());
+    assert_eq!(
+        t.render(),
+        "x 2024-09-01 Pay rent\n# bills\nCall mom\nWater plants\n"
+    );
+    assert!(!t.history.can_undo());
+    assert!(!t.is_modified_on_disk().unwrap());
+}
+
+#[test]
+fn test_done_path() {
+    let mut t = TodoFile::from_string("");
+    t.path = PathBuf::from("lists/todo.txt");
+    assert_eq!(t.done_path(), PathBuf::from("lists/done.txt"));
+    t.done_path = Some(PathBuf::from("archive.txt"));
+    assert_eq!(t.done_path(), PathBuf::from("archive.txt"));
+    assert!(t.done().unwrap().is_empty());
+}
@@ -0,0 +1,265 @@
+//! # Todo Builder
+//!
+//! A fluent way to create a `parser::Todo` from its parts instead of formatting a todo.txt line
+//! by hand.
+//! The builder writes the line in the order the todo.txt format expects and parses it back, so
+//! the `content` of the todo item always matches its fields. Combinations the format can't
+//! represent are rejected when `build` is called.
+//!
+//! ```rust
+//! use libdonow::{builder::TodoBuilder, priority::Priority};
+//!
+//! let due = chrono::NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+//! let t = TodoBuilder::new("Call mom")
+//!     .priority(Priority::A)
+//!     .project("family")
+//!     .context("phone")
+//!     .due(due)
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(t.content, "(A) Call mom +family @phone due:2024-09-01");
+//! ```
+
+use chrono::NaiveDate;
+
+use crate::{
+    parser::{Todo, TodoErr},
+    priority::Priority,
+    tags::Tags,
+    token,
+};
+
+/// A builder for a `parser::Todo`.
+/// Create one with `TodoBuilder::new` or `Todo::builder`, chain the parts and call `build`.
+#[derive(Debug, Clone, Default)]
+pub struct TodoBuilder {
+    title: String,
+    completed: bool,
+    priority: Option<Priority>,
+    completion: Option<NaiveDate>,
+    creation: Option<NaiveDate>,
+    projects: Vec<String>,
+    contexts: Vec<String>,
+    tags: Tags,
+}
+
+impl TodoBuilder {
+    /// Creates a builder for a todo item with the given title.
+    pub fn new(title: &str) -> Self {
+        TodoBuilder {
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets whether the todo item is completed.
+    pub fn completed(mut self, completed: bool) -> Self {
+        self.completed = completed;
+        self
+    }
+
+    /// Sets the priority of the todo item.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Sets the completion date of the todo item.
+    /// The todo item has to be completed as well.
+    pub fn completion(mut self, date: NaiveDate) -> Self {
+        self.completion = Some(date);
+        self
+    }
+
+    /// Sets the creation date of the todo item.
+    pub fn creation(mut self, date: NaiveDate) -> Self {
+        self.creation = Some(date);
+        self
+    }
+
+    /// Sets the due date of the todo item as a `due:` tag.
+    pub fn due(mut self, date: NaiveDate) -> Self {
+        self.tags.set("due", &date.to_string());
+        self
+    }
+
+    /// Sets the threshold date of the todo item as a `t:` tag.
+    pub fn threshold(mut self, date: NaiveDate) -> Self {
+        self.tags.set("t", &date.to_string());
+        self
+    }
+
+    /// Adds a project, given without the leading `+`.
+    pub fn project(mut self, project: &str) -> Self {
+        if !self.projects.iter().any(|p| p == project) {
+            self.projects.push(project.to_string());
+        }
+        self
+    }
+
+    /// Adds a context, given without the leading `@`.
+    pub fn context(mut self, context: &str) -> Self {
+        if !self.contexts.iter().any(|c| c == context) {
+            self.contexts.push(context.to_string());
+        }
+        self
+    }
+
+    /// Adds a `key:value` tag, keeping any other values the key already has.
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.append(key, value);
+        self
+    }
+
+    /// Builds the todo item.
+    ///
+    /// Returns a `TodoErr::NoTitle` error if the title is empty and a `TodoErr::InvalidTodo`
+    /// error if:
+    ///
+    /// - there is a completion date but the todo item isn't completed,
+    /// - the completion date is before the creation date,
+    /// - the todo item is completed and has a creation date but no completion date, which the
+    ///   format would read back as the completion date,
+    /// - a project, context or tag wouldn't be read back as one,
+    /// - the title would be read back as something else, like `x` turning into the completion
+    ///   marker or `+word` into a project.
+    pub fn build(self) -> Result<Todo, TodoErr> {
+        if self.completion.is_some() && !self.completed {
+            return Err(invalid(
+                "a completion date needs the todo item to be completed",
+            ));
+        }
+        match (self.creation, self.completion) {
+            (Some(_), None) if self.completed => {
+                return Err(invalid(
+                    "a completed todo item with a creation date needs a completion date",
+                ));
+            }
+            (Some(creation), Some(completion)) if completion < creation => {
+                return Err(invalid("the completion date is before the creation date"));
+            }
+            _ => {}
+        }
+        if let Some(p) = self.projects.iter().find(|p| !token::is_name(p)) {
+            return Err(invalid(&format!("`+{}` isn't a valid project", p)));
+        }
+        if let Some(c) = self.contexts.iter().find(|c| !token::is_name(c)) {
+            return Err(invalid(&format!("`@{}` isn't a valid context", c)));
+        }
+
+        let line = self.line();
+        let t = Todo::parse(&line)?;
+
+        let title = self.title.split_whitespace().collect::<Vec<_>>().join(" ");
+        if t.title != title
+            || t.completed != self.completed
+            || t.priority != self.priority
+            || t.creation != self.creation
+            || t.completion != self.completion
+        {
+            return Err(invalid(&format!(
+                "`{}` isn't read back as the title",
+                title
+            )));
+        }
+        if t.projects != self.projects || t.contexts != self.contexts || t.others != self.tags {
+            return Err(invalid(&format!(
+                "the projects, contexts or tags of `{}` aren't read back as given",
+                line
+            )));
+        }
+
+        Ok(t)
+    }
+
+    /// Writes the parts in the order the todo.txt format expects.
+    fn line(&self) -> String {
+        let mut parts = Vec::new();
+        if self.completed {
+            parts.push("x".to_string());
+        }
+        if let Some(p) = self.priority {
+            parts.push(format!("({})", p));
+        }
+        if let Some(d) = self.completion {
+            parts.push(d.to_string());
+        }
+        if let Some(d) = self.creation {
+            parts.push(d.to_string());
+        }
+        parts.extend(self.title.split_whitespace().map(str::to_string));
+        parts.extend(self.projects.iter().map(|p| format!("+{}", p)));
+        parts.extend(self.contexts.iter().map(|c| format!("@{}", c)));
+        parts.extend(self.tags.iter().map(|(k, v)| format!("{}:{}", k, v)));
+
+        parts.join(" ")
+    }
+}
+
+fn invalid(reason: &str) -> TodoErr {
+    TodoErr::InvalidTodo(reason.to_string())
+}
+
+#[cfg(test)]
+#[test]
+fn test_build() {
+    let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+    let t = TodoBuilder::new("Pay  rent")
+        .completed(true)
+        .completion(date(9, 2))
+        .creation(date(8, 20))
+        .project("home.bills")
+        .threshold(date(8, 25))
+        .due(date(9, 1))
+        .tag("dep", "3")
+        .tag("dep", "4")
+        .build()
+        .unwrap();
+    assert_eq!(
+        t.content,
+        "x 2024-09-02 2024-08-20 Pay rent +home.bills t:2024-08-25 due:2024-09-01 dep:3 dep:4"
+    );
+    assert_eq!(t.to_string(), t.content);
+    assert_eq!(t.title, "Pay rent");
+    assert_eq!(t.parse_due().unwrap(), Some(date(9, 1)));
+
+    let t = TodoBuilder::new("Call mom")
+        .completed(true)
+        .completion(date(9, 1))
+        .build()
+        .unwrap();
+    assert_eq!(t.content, "x 2024-09-01 Call mom");
+    assert_eq!(t.completion, Some(date(9, 1)));
+    assert_eq!(t.creation, None);
+}
+
+#[test]
+fn test_build_rejects() {
+    let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+    let e = TodoBuilder::new("Pay rent").completion(date(9, 2)).build();
+    assert!(matches!(e, Err(TodoErr::InvalidTodo(_))));
+    let e = TodoBuilder::new("Pay rent")
+        .completed(true)
+        .creation(date(9, 3))
+        .completion(date(9, 2))
+        .build();
+    assert!(matches!(e, Err(TodoErr::InvalidTodo(_))));
+    let e = TodoBuilder::new("Pay rent")
+        .completed(true)
+        .creation(date(8, 20))
+        .build();
+    assert_eq!(
+        e.unwrap_err().to_string(),
+        "invalid todo item: a completed todo item with a creation date needs a completion date"
+    );
+    let e = TodoBuilder::new("  ").build();
+    assert!(matches!(e, Err(TodoErr::NoTitle { .. })));
+    let e = TodoBuilder::new("Pay rent").project("two words").build();
+    assert!(matches!(e, Err(TodoErr::InvalidTodo(_))));
+    let e = TodoBuilder::new("x marks the spot").build();
+    assert!(matches!(e, Err(TodoErr::InvalidTodo(_))));
+    let e = TodoBuilder::new("Pay +rent").build();
+    assert!(matches!(e, Err(TodoErr::InvalidTodo(_))));
+    let e = TodoBuilder::new("Pay rent").tag("url", "//x").build();
+    assert!(matches!(e, Err(TodoErr::InvalidTodo(_))));
+}
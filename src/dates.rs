@@ -0,0 +1,90 @@
+//! # Relative Dates
+//!
+//! Resolves the relative dates that can be typed in place of a `YYYY-MM-DD` date in the
+//! `due:` and `t:` tags, like `due:tomorrow` or `t:+2w`.
+//! Relative dates are resolved against a reference date that is passed in, so that the result
+//! doesn't depend on when the code runs. `TodoFile` rewrites them into absolute dates before
+//! the file is saved, so other todo.txt applications only ever see `YYYY-MM-DD` dates.
+//!
+//! The supported forms are:
+//!
+//! - `today`, `tomorrow` and `yesterday`.
+//! - A weekday like `fri` or `friday`, which is the next such day after the reference date.
+//! - `+3d`, `+2b`, `+1w`, `+1m` or `+1y` to move forward by days, business days, weeks, months
+//!   or years. See the `recur` module for how each unit moves.
+//! - `eom` and `eoy` for the last day of the month or the year.
+//!
+//! ```rust
+//! use libdonow::dates::resolve;
+//!
+//! // a sunday
+//! let today = chrono::NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+//! assert_eq!(resolve("fri", today), chrono::NaiveDate::from_ymd_opt(2024, 9, 6));
+//! assert_eq!(resolve("+2w", today), chrono::NaiveDate::from_ymd_opt(2024, 9, 15));
+//! assert_eq!(resolve("eom", today), chrono::NaiveDate::from_ymd_opt(2024, 9, 30));
+//! ```
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+
+use crate::{parser::parse_date, recur::Recurrence};
+
+/// The keys of the tags whose values can be relative dates.
+pub const DATE_TAGS: [&str; 2] = ["due", "t"];
+
+/// Resolves a date against the reference date.
+/// Absolute `YYYY-MM-DD` dates are returned as they are. Returns `None` if the value isn't a
+/// date in any of the supported forms.
+pub fn resolve(value: &str, today: NaiveDate) -> Option<NaiveDate> {
+    parse_date(value).or_else(|| resolve_relative(value, today))
+}
+
+/// Resolves a relative date against the reference date.
+/// Returns `None` for absolute dates and for values that aren't dates.
+pub fn resolve_relative(value: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let value = value.to_ascii_lowercase();
+    match value.as_str() {
+        "today" => Some(today),
+        "tomorrow" => today.succ_opt(),
+        "yesterday" => today.pred_opt(),
+        "eom" => {
+            let first = NaiveDate::from_ymd_opt(today.year(), today.month(), 1)?;
+            first.checked_add_months(chrono::Months::new(1))?.pred_opt()
+        }
+        "eoy" => NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        v => match v.strip_prefix('+') {
+            Some(offset) => offset
+                .parse::<Recurrence>()
+                .ok()
+                .and_then(|rec| rec.advance(today)),
+            None => next_weekday(v.parse().ok()?, today),
+        },
+    }
+}
+
+/// Returns the next day after the reference date that falls on the weekday.
+fn next_weekday(weekday: Weekday, today: NaiveDate) -> Option<NaiveDate> {
+    let ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    let ahead = if ahead == 0 { 7 } else { ahead };
+    today.checked_add_days(Days::new(ahead.into()))
+}
+
+#[cfg(test)]
+#[test]
+fn test_resolve() {
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+    // a friday
+    let today = date(2024, 2, 2).unwrap();
+    assert_eq!(resolve("today", today), Some(today));
+    assert_eq!(resolve("Tomorrow", today), date(2024, 2, 3));
+    assert_eq!(resolve("fri", today), date(2024, 2, 9));
+    assert_eq!(resolve("monday", today), date(2024, 2, 5));
+    assert_eq!(resolve("+3d", today), date(2024, 2, 5));
+    assert_eq!(resolve("+1b", today), date(2024, 2, 5));
+    assert_eq!(resolve("+2w", today), date(2024, 2, 16));
+    assert_eq!(resolve("eom", today), date(2024, 2, 29));
+    assert_eq!(resolve("eoy", today), date(2024, 12, 31));
+    assert_eq!(resolve("2024-03-01", today), date(2024, 3, 1));
+    assert_eq!(resolve_relative("2024-03-01", today), None);
+    assert_eq!(resolve("soon", today), None);
+    assert_eq!(resolve("+3", today), None);
+}
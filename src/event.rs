@@ -0,0 +1,260 @@
+//! # Events
+//!
+//! Incremental reloading of a `TodoFile` that was changed on disk.
+//! `TodoFile::reload` only parses the lines that changed and describes what happened to the
+//! todo items as a list of `TodoEvent`s, so an application can update its view of the file
+//! instead of redrawing everything. With the `watch` feature, the `watch` module calls it
+//! whenever the file changes.
+//!
+//! Changes in memory that weren't saved yet are kept: the reload is a three-way merge between
+//! the file as it was read, the todo items in memory and the file on disk, like
+//! `TodoFile::merge_disk`. Lines changed differently in memory and on disk keep the version in
+//! memory and are reported as a `TodoEvent::Conflict`.
+//!
+//! ```rust,no_run
+//! use libdonow::{event::TodoEvent, file::TodoFile};
+//!
+//! let mut file = TodoFile::new("todo.txt");
+//! for event in file.reload().expect("Failed to read todo.txt") {
+//!     match event {
+//!         TodoEvent::Added { index, todo } => println!("added {}: {}", index, todo),
+//!         TodoEvent::Removed { index, todo } => println!("removed {}: {}", index, todo),
+//!         TodoEvent::Modified { index, new, .. } => println!("changed {}: {}", index, new),
+//!         TodoEvent::Conflict(c) => println!("kept line {} over {:?}", c.line, c.theirs),
+//!     }
+//! }
+//! ```
+
+use std::io;
+
+use crate::{
+    file::{RawKind, RawLine, TodoFile},
+    merge,
+    parser::Todo,
+};
+
+/// A change to the todo items of a `TodoFile`.
+/// The events of a reload are in order and each index is the index in the todo items after
+/// the events before it were applied. This way applying the events one by one to a copy of
+/// the old todo items gives the new ones, and the indices of `Added` and `Modified` events are
+/// the indices in the reloaded file. A modified todo item keeps its id, see the `id` module.
+/// Conflicts don't change the todo items and come after the other events.
+#[derive(Debug, Clone)]
+pub enum TodoEvent {
+    /// A todo item was added at the index.
+    Added {
+        /// The index of the new todo item.
+        index: usize,
+        /// The new todo item.
+        todo: Todo,
+    },
+    /// The todo item at the index was removed.
+    Removed {
+        /// The index of the removed todo item.
+        index: usize,
+        /// The removed todo item.
+        todo: Todo,
+    },
+    /// The todo item at the index was replaced with a changed version.
+    Modified {
+        /// The index of the todo item.
+        index: usize,
+        /// The todo item before the change.
+        old: Todo,
+        /// The todo item after the change.
+        new: Todo,
+    },
+    /// Lines were changed differently in memory and on disk. The version in memory was kept,
+    /// the version on disk is in the conflict.
+    Conflict(merge::Conflict),
+}
+
+/// A line of the file in memory, as `TodoFile::render` writes it.
+enum Item {
+    Todo(Todo),
+    Raw(RawLine),
+}
+
+impl TodoFile {
+    /// Reloads the file from disk and returns what changed.
+    ///
+    /// The changes made on disk since the file was read are merged into the todo items in
+    /// memory, see the module documentation. The merged file is compared line by line with the
+    /// file in memory as `render` writes it. The todo items and raw lines of the lines that
+    /// stayed the same are kept as they are, only the lines that changed are parsed. A file that
+    /// doesn't exist is read as empty. The history of the file is cleared when anything
+    /// changed.
+    ///
+    /// The file on disk becomes the new base, so `save` writes the merged file afterwards.
+    pub fn reload(&mut self) -> io::Result<Vec<TodoEvent>> {
+        let disk = self.read_disk()?;
+        let rendered = self.render();
+        let (merged, report) = merge::merge(&self.content, &rendered, &disk);
+        let mut events = if merged == rendered {
+            Vec::new()
+        } else {
+            self.history.clear();
+            self.apply(&rendered, &merged)
+        };
+        events.extend(report.conflicts.into_iter().map(TodoEvent::Conflict));
+
+        self.content = disk;
+        self.loaded_hash = Some(merge::hash(&self.content));
+        Ok(events)
+    }
+
+    /// Replaces the todo items and raw lines rendered as `old` with the lines of `new`.
+    fn apply(&mut self, old: &str, new: &str) -> Vec<TodoEvent> {
+        let old_lines = old.lines().collect::<Vec<_>>();
+        let new_lines = new.lines().collect::<Vec<_>>();
+
+        // the items in the order `render` writes them
+        let mut items = Vec::with_capacity(old_lines.len());
+        let mut raw = std::mem::take(&mut self.raw).into_iter().peekable();
+        for (i, todo) in std::mem::take(&mut self.todos).into_iter().enumerate() {
+            while let Some(r) = raw.next_if(|r| r.position <= i) {
+                items.push(Some(Item::Raw(r)));
+            }
+            items.push(Some(Item::Todo(todo)));
+        }
+        items.extend(raw.map(|r| Some(Item::Raw(r))));
+
+        let mut todos = Vec::new();
+        let mut raws = Vec::new();
+        let mut events = Vec::new();
+        let (mut o, mut n) = (0, 0);
+        let pairs = merge::matching(&old_lines, &new_lines);
+        for &(po, pn) in pairs
+            .iter()
+            .chain([(old_lines.len(), new_lines.len())].iter())
+        {
+            let mut removed = items[o..po]
+                .iter_mut()
+                .filter_map(|item| match item.take() {
+                    Some(Item::Todo(todo)) => Some(todo),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .into_iter();
+            let mut added = Vec::new();
+            for (j, line) in new_lines.iter().enumerate().take(pn).skip(n) {
+                match self.parse_line(j, line) {
+                    Ok(todo) => added.push(todo),
+                    Err(kind) => raws.push(RawLine {
+                        position: todos.len() + added.len(),
+                        content: line.to_string(),
+                        kind,
+                    }),
+                }
+            }
+
+            let mut added = added.into_iter();
+            loop {
+                let index = todos.len();
+                match (removed.next(), added.next()) {
+                    (Some(old), Some(mut new)) => {
+                        new.keep_id(&old);
+                        todos.push(new.clone());
+                        events.push(TodoEvent::Modified { index, old, new });
+                    }
+                    (Some(todo), None) => events.push(TodoEvent::Removed { index, todo }),
+                    (None, Some(todo)) => {
+                        todos.push(todo.clone());
+                        events.push(TodoEvent::Added { index, todo });
+                    }
+                    (None, None) => break,
+                }
+            }
+
+            match items.get_mut(po).and_then(Option::take) {
+                Some(Item::Todo(todo)) => todos.push(todo),
+                Some(Item::Raw(mut r)) => {
+                    r.position = todos.len();
+                    if let RawKind::Invalid(e) = r.kind {
+                        r.kind = RawKind::Invalid(e.at_line(pn + 1));
+                    }
+                    raws.push(r);
+                }
+                None => {}
+            }
+            (o, n) = (po + 1, pn + 1);
+        }
+
+        self.todos = todos;
+        self.raw = raws;
+        self.dedup_ids();
+        events
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_reload() {
+    let dir = crate::file::TempDir::new("reload");
+    let path = dir.join("todo.txt");
+    std::fs::write(&path, "Call mom\n# chores\nPay rent\nWater plants\n").unwrap();
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    assert!(t.reload().unwrap().is_empty());
+
+    std::fs::write(
+        &path,
+        "Call mom\n# chores\nx Pay rent\n2024-13-45 Broken date\nWater plants\nBuy milk\n",
+    )
+    .unwrap();
+    let events = t.reload().unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(matches!(
+        &events[0],
+        TodoEvent::Modified { index: 1, old, new } if old.title == "Pay rent" && new.completed
+    ));
+    assert!(matches!(
+        &events[1],
+        TodoEvent::Added { index: 3, todo } if todo.title == "Buy milk"
+    ));
+    assert_eq!(t.len(), 4);
+    assert_eq!(t.raw.len(), 2);
+    assert_eq!(t.render(), t.content);
+
+    std::fs::write(&path, "# chores\nWater plants\nBuy milk\n").unwrap();
+    let events = t.reload().unwrap();
+    let removed = events
+        .iter()
+        .map(|e| match e {
+            TodoEvent::Removed { index, todo } => (*index, todo.title.as_str()),
+            e => panic!("unexpected {:?}", e),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(removed, vec![(0, "Call mom"), (0, "Pay rent")]);
+    assert_eq!(t.render(), t.content);
+}
+
+#[test]
+fn test_reload_keeps_unsaved_changes() {
+    let dir = crate::file::TempDir::new("reload_keeps_unsaved_changes");
+    let path = dir.join("todo.txt");
+    std::fs::write(&path, "Call mom\nPay rent\n").unwrap();
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    t[0].set_title("Unsaved edit").unwrap();
+    std::fs::write(&path, "Call mom\nPay rent +home\n").unwrap();
+    let events = t.reload().unwrap();
+    assert!(matches!(
+        events.as_slice(),
+        [TodoEvent::Modified { index: 1, new, .. }] if new.projects == ["home"]
+    ));
+    assert_eq!(t.render(), "Unsaved edit\nPay rent +home\n");
+
+    std::fs::write(&path, "Call dad\nPay rent +home\n").unwrap();
+    let events = t.reload().unwrap();
+    assert!(matches!(
+        events.as_slice(),
+        [TodoEvent::Conflict(c)] if c.theirs == ["Call dad"]
+    ));
+    assert_eq!(t[0].title, "Unsaved edit");
+    t.save().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "Unsaved edit\nPay rent +home\n"
+    );
+}
@@ -1,5 +1,5 @@
 //! # TodoFile
-//! 
+//!
 //! This module can be used to read and write todo.txt files.
 //! It simplifies and abstracts away the complexities by introducing a simple struct that can be
 //! used to interact with todo.txt files.
@@ -20,15 +20,22 @@
 //! However, for more intricate handling of todo items, you can use the `parser::Todo` struct,
 //! which is used internally by this struct.
 
-
 use std::{
+    ffi::OsString,
     fmt::Display,
+    io::{self, Write},
     path::{Path, PathBuf},
 };
 
 use fancy_regex::Regex;
 
-use crate::parser;
+use crate::{
+    history::{Command, History},
+    id, merge, parallel,
+    parser::{self, TodoErr},
+    token, tree,
+    view::TodoView,
+};
 
 /// A struct that represents a todo.txt file.
 /// This struct doesn't actually represent a file on disk, but rather a collection of todos.
@@ -41,6 +48,59 @@ pub struct TodoFile {
     pub todos: Vec<parser::Todo>,
     /// The content of the file as a string.
     pub content: String,
+    /// The mode used to parse each line of the file.
+    /// Change it and call `load` to reparse the file with a different mode.
+    pub mode: parser::ParseMode,
+    /// The lines of the file that aren't todo items.
+    /// These are kept so that saving the file doesn't lose them.
+    pub raw: Vec<RawLine>,
+    /// The date relative dates like `due:tomorrow` are resolved against.
+    /// Uses the local date when it isn't set.
+    pub today: Option<chrono::NaiveDate>,
+    /// The separator between the levels of hierarchical project and context names, like the
+    /// `.` in `+work.backend`. It is `.` by default and is changed with `set_separator`. See the
+    /// `tree` module.
+    pub(crate) separator: char,
+    /// Whether `save` keeps the previous version of the file as `<path>.bak`.
+    pub backup: bool,
+    /// The path of the archive completed todo items are moved to by `archive`.
+    /// Uses `done.txt` next to the file when it isn't set. See the `archive` module.
+    pub done_path: Option<PathBuf>,
+    /// The hash of the file as it was last read from or written to disk.
+    /// `save` uses it to notice changes made by other programs, see the `merge` module. It is
+    /// `None` when the file wasn't read from disk, `save` doesn't check for changes then.
+    pub loaded_hash: Option<u64>,
+    /// The changes that can be undone and redone, see the `history` module.
+    /// Reading the file again with `load` or `reload` clears it.
+    pub history: History,
+}
+
+/// The default separator of hierarchical project and context names.
+pub const DEFAULT_SEPARATOR: char = '.';
+
+/// A line of a todo.txt file that isn't a todo item.
+/// Blank lines, comments and lines that failed to parse are kept as they were read and are
+/// written back in place when the file is saved.
+#[derive(Debug)]
+pub struct RawLine {
+    /// The number of todo items before the line in the file.
+    /// The line is written back right before the todo item at this index.
+    pub position: usize,
+    /// The line as it was read.
+    pub content: String,
+    /// The kind of the line.
+    pub kind: RawKind,
+}
+
+/// The kind of a `RawLine`.
+#[derive(Debug)]
+pub enum RawKind {
+    /// A line with only whitespace in it.
+    Blank,
+    /// A line starting with `# `.
+    Comment,
+    /// A line that failed to parse, along with the error it failed with.
+    Invalid(TodoErr),
 }
 
 impl TodoFile {
@@ -49,38 +109,32 @@ impl TodoFile {
     /// For more granular control flow of the file, you can use the from_path method.
     pub fn new(path: &str) -> Self {
         let path = PathBuf::from(path);
-        let content = match std::fs::read_to_string(&path) {
-            Ok(content) => content,
-            Err(_) => String::new(),
-        };
-
-        let mut t = TodoFile::from_path(&path).unwrap_or_else(|_| TodoFile {
-            path,
-            todos: Vec::new(),
-            content,
-        });
-        t.load();
-
-        t
+        TodoFile::from_path(&path).unwrap_or_else(|_| {
+            let mut t = TodoFile::from_string("");
+            t.path = path;
+            t.loaded_hash = Some(merge::hash(&t.content));
+            t
+        })
     }
 
     /// Reads off a path to a file and returns a `TodoFile` struct.
-    /// If the file doesn't exist, it returns an IO error.
+    /// If the file doesn't exist or can't be read, it returns a `TodoErr::Io` error.
     /// This method is useful when you want to handle the error explicitly.
     /// Otherwise, you can use the `new` method.
-    pub fn from_path(path: &Path) -> Result<Self, std::io::Error> {
-        if !path.exists() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "File not found",
-            ));
-        }
-
-        let content = std::fs::read_to_string(path).unwrap();
+    pub fn from_path(path: &Path) -> Result<Self, TodoErr> {
+        let content = std::fs::read_to_string(path)?;
         let mut t = TodoFile {
             path: PathBuf::from(path),
             todos: Vec::new(),
+            loaded_hash: Some(merge::hash(&content)),
             content,
+            mode: parser::ParseMode::default(),
+            raw: Vec::new(),
+            today: None,
+            separator: DEFAULT_SEPARATOR,
+            backup: false,
+            done_path: None,
+            history: History::default(),
         };
         t.load();
 
@@ -96,6 +150,14 @@ impl TodoFile {
             path: PathBuf::new(),
             todos: Vec::new(),
             content,
+            mode: parser::ParseMode::default(),
+            raw: Vec::new(),
+            today: None,
+            separator: DEFAULT_SEPARATOR,
+            backup: false,
+            done_path: None,
+            loaded_hash: None,
+            history: History::default(),
         };
         t.load();
 
@@ -104,71 +166,256 @@ impl TodoFile {
 
     /// The main function that parses each line of the file and stores it in the `todos` vector.
     /// The line is determined by the `Line` struct as defined by rust's standard library.
-    /// Each line is passed into the parse method of the `Todo` struct, which returns a `Result`.
-    /// Any errors are ignored and the loop continues.
+    /// Each line is passed into the parse method of the `Todo` struct along with the `mode` of
+    /// the file, which returns a `Result`.
+    /// Blank lines, comments and lines that fail to parse are stored in the `raw` vector along
+    /// with their error, so that `save` can write them back.
+    ///
+    /// The todo items read again keep the ids of the todo items they replace, only new lines
+    /// get new ids, see the `id` module.
     pub fn load(&mut self) {
-        let lines = self.content.lines();
         let mut todos = Vec::new();
+        let mut raw = Vec::new();
 
-        for line in lines {
-            match parser::Todo::parse(line) {
+        let lines = self.content.lines().collect::<Vec<_>>();
+        let mode = self.mode;
+        for (line, parsed) in lines
+            .iter()
+            .zip(parallel::map(&lines, |i, line| parse_line(i, line, mode)))
+        {
+            match parsed {
                 Ok(todo) => todos.push(todo),
-                Err(_) => continue,
+                Err(kind) => raw.push(RawLine {
+                    position: todos.len(),
+                    content: line.to_string(),
+                    kind,
+                }),
             }
         }
 
+        id::carry_over(&self.todos, &mut todos);
         self.todos = todos;
+        self.raw = raw;
+        self.history.clear();
+        self.dedup_ids();
+    }
+
+    /// Parses a single line of the file, `i` is the 0-based index of the line.
+    /// Returns the kind of raw line for blank lines, comments and lines that fail to parse.
+    pub(crate) fn parse_line(&self, i: usize, line: &str) -> Result<parser::Todo, RawKind> {
+        parse_line(i, line, self.mode)
+    }
+
+    /// Returns the errors of the lines that failed to parse.
+    /// Each error carries the 1-based line number and the byte span in that line.
+    pub fn diagnostics(&self) -> Vec<&TodoErr> {
+        self.raw
+            .iter()
+            .filter_map(|r| match &r.kind {
+                RawKind::Invalid(e) => Some(e),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Formats the file the way it is written to disk.
+    /// The todo items are formatted using the `Display` trait and the raw lines are put back
+    /// in between them.
+    pub fn render(&self) -> String {
+        let mut content = String::new();
+        let mut raw = self.raw.iter().peekable();
+        for (i, todo) in self.todos.iter().enumerate() {
+            while let Some(r) = raw.next_if(|r| r.position <= i) {
+                content.push_str(&r.content);
+                content.push('\n');
+            }
+            content.push_str(format!("{}\n", todo).as_str());
+        }
+        for r in raw {
+            content.push_str(&r.content);
+            content.push('\n');
+        }
+
+        content
     }
 
     /// Saves the `TodoFile` struct to the file on disk.
     /// The path has to be set before calling this method.
     /// Alternatively, you can use the `save_as` method to save the file to a different path.
-    /// The todo's are formatted using the `Display` trait and written to the file.
-    pub fn save(&self) {
-        let mut content = String::new();
-        for todo in &self.todos {
-            content.push_str(format!("{}\n", todo).as_str());
+    /// Relative dates are rewritten using `resolve_dates` first, then the file is formatted
+    /// using the `render` method and written to disk.
+    ///
+    /// The save is atomic: the file is written to a temporary file in the same directory,
+    /// synced to disk and then renamed over the old file, so a crash or a full disk never
+    /// leaves a half written file behind. The permissions of the old file are kept. If `backup`
+    /// is set, the old file is copied to `<path>.bak` first. A symlink is followed, so the file
+    /// it points to is replaced.
+    /// Returns an error if the file can't be written, the old file is left untouched then. An
+    /// error syncing the directory after the new file took the place of the old one is
+    /// returned as well, but the `TodoFile` already knows the new file as the one on disk.
+    ///
+    /// If the file was changed by another program since it was read, the save fails with a
+    /// `merge::ModifiedOnDisk` error instead of overwriting the changes. Use `merge_disk` or
+    /// `save_merged` to bring the changes in first.
+    pub fn save(&mut self) -> io::Result<()> {
+        if self.is_modified_on_disk()? {
+            return Err(io::Error::other(merge::ModifiedOnDisk {
+                path: self.path.clone(),
+            }));
         }
 
-        std::fs::write(&self.path, content).unwrap();
+        self.resolve_dates();
+        let content = self.render();
+        let written = replace(&self.path, content.as_bytes(), self.backup)?;
+        // the file on disk is the new one from here on, even if syncing the directory fails
+        self.content = content;
+        self.loaded_hash = Some(merge::hash(&self.content));
+        sync_dir(&written)?;
+        if self.history.persist {
+            self.save_history()?;
+        }
+        Ok(())
     }
 
     /// Saves the `TodoFile` struct to a different file on disk.
-    /// Works the same as the `save` method, but you can specify a different path.
-    pub fn save_as(&self, path: &str) {
-        let mut content = String::new();
-        for todo in &self.todos {
-            content.push_str(format!("{}\n", todo).as_str());
-        }
+    /// Works the same as the `save` method, but you can specify a different path. The path
+    /// isn't checked for changes made by other programs.
+    pub fn save_as(&mut self, path: &str) -> io::Result<()> {
+        self.resolve_dates();
+        write_atomic(Path::new(path), self.render().as_bytes(), self.backup)
+    }
 
-        std::fs::write(path, content).unwrap();
+    /// Returns the date relative dates are resolved against.
+    /// This is the `today` field, or the local date when it isn't set.
+    pub fn today(&self) -> chrono::NaiveDate {
+        self.today
+            .unwrap_or_else(|| chrono::Local::now().naive_local().date())
+    }
+
+    /// Rewrites the relative dates like `due:tomorrow` of every todo item into `YYYY-MM-DD`
+    /// dates, resolving them against `today`. See `parser::Todo::resolve_dates`.
+    /// Returns the number of todo items that changed.
+    pub fn resolve_dates(&mut self) -> usize {
+        let today = self.today();
+        self.todos
+            .iter_mut()
+            .map(|t| t.resolve_dates(today))
+            .filter(|changed| *changed)
+            .count()
     }
 
     /// Changes the status of a todo item.
     /// The index is the index of the todo item in the `todos` vector.
-    /// The status is toggled between completed and not completed.
+    /// An open todo item is completed on the date returned by `today` and a completed one is
+    /// reopened, see `parser::Todo::complete` and `parser::Todo::reopen`. A recurring todo
+    /// item isn't copied, use `complete` for that.
     pub fn change_status(&mut self, index: usize) {
-        self.todos[index].toggle_status();
+        let today = self.today();
+        self.modify(index, |t| {
+            if t.completed {
+                t.reopen();
+            } else {
+                t.complete(today);
+            }
+        });
+    }
+
+    /// Completes a todo item on the given date.
+    /// See `parser::Todo::complete` for how the todo item changes.
+    ///
+    /// If the todo item recurs with a `rec:` tag, its next occurrence is appended to the file
+    /// and its index is returned. See `parser::Todo::next_recurrence` for how the dates of
+    /// the copy are moved. Nothing changes if the recurrence or its dates fail to parse.
+    pub fn complete(
+        &mut self,
+        index: usize,
+        on: chrono::NaiveDate,
+    ) -> Result<Option<usize>, TodoErr> {
+        if self.todos[index].completed {
+            return Ok(None);
+        }
+
+        self.flush_edits();
+        let next = self.todos[index].next_recurrence(on)?;
+        let old = self.todos[index].clone();
+        self.todos[index].complete(on);
+        let mut commands = vec![Command::Replace {
+            index,
+            old,
+            new: self.todos[index].clone(),
+        }];
+
+        let next = next.map(|next| {
+            let index = self.push(next);
+            commands.push(Command::Insert {
+                index,
+                todo: self.todos[index].clone(),
+                positions: self.raw_positions(),
+            });
+            index
+        });
+        self.record(commands);
+
+        Ok(next)
+    }
+
+    /// Reopens a completed todo item.
+    /// See `parser::Todo::reopen` for how the todo item changes.
+    pub fn reopen(&mut self, index: usize) {
+        self.modify(index, parser::Todo::reopen);
     }
 
     /// Removes a todo item from the `todos` vector.
+    /// The raw lines after it move up with the rest of the file.
     pub fn remove(&mut self, index: usize) {
-        self.todos.remove(index);
+        self.flush_edits();
+        let positions = self.raw_positions();
+        let todo = self.todos[index].clone();
+        self.take(index);
+        self.record(vec![Command::Remove {
+            index,
+            todo,
+            positions,
+        }]);
     }
 
     /// Adds a todo item to the `todos` vector.
+    /// Relative dates like `due:tomorrow` are rewritten against `today` as the todo item is
+    /// added.
     pub fn add(&mut self, todo: parser::Todo) {
+        self.flush_edits();
+        let index = self.push(todo);
+        self.record(vec![Command::Insert {
+            index,
+            todo: self.todos[index].clone(),
+            positions: self.raw_positions(),
+        }]);
+    }
+
+    /// Adds a todo item without recording it and returns its index.
+    /// A todo item that has the id of one in the file already gets a new one.
+    fn push(&mut self, mut todo: parser::Todo) -> usize {
+        todo.resolve_dates(self.today());
+        todo.sync();
+        if self.position(todo.id()).is_some() {
+            todo.renumber();
+        }
         self.todos.push(todo);
+        self.todos.len() - 1
     }
 
     /// Updates a todo item in the `todos` vector.
     /// Doesn't do anything if the index is out of bounds.
-    pub fn update(&mut self, index: usize, todo: parser::Todo) {
+    /// The content of the todo item is synced with its fields, see `parser::Todo::sync`. The
+    /// todo item keeps the id of the one it replaces unless it has an `id:` tag of its own.
+    pub fn update(&mut self, index: usize, mut todo: parser::Todo) {
         if index >= self.todos.len() {
             return;
         }
 
-        self.todos[index] = todo;
+        todo.sync();
+        todo.keep_id(&self.todos[index]);
+        self.modify(index, |t| *t = todo);
     }
 
     /// Gets a todo item from the `todos` vector.
@@ -198,19 +445,21 @@ impl TodoFile {
     /// The rearrangement is done by sorting the todo items sequentially in the following
     /// order:
     ///
-    /// 1. Not completed todo items sorted by creation date.
+    /// 1. Not completed todo items sorted by priority, `(A)` first and no priority last, and
+    ///    then by creation date.
     /// 2. Completed todo items sorted by completion date.
     /// 3. Merged list of the two categories.
     ///
     /// This behavior is inspired by the todo.txt cli application.
     pub fn rearrange(&mut self) -> Vec<parser::Todo> {
-        // get all the todo's that are not done, sort them by creation date
+        // get all the todo's that are not done, sort them by priority and then creation date
+        // todo's without a priority go after the ones with a priority
         let mut not_done = self
             .todos
             .iter()
             .filter(|e| !e.completed)
             .collect::<Vec<&parser::Todo>>();
-        not_done.sort_by(|a, b| a.creation.cmp(&b.creation));
+        not_done.sort_by_key(|e| (e.priority.is_none(), e.priority, e.creation));
 
         // get all the todo's that are done, sort them by completion date
         let mut done = self
@@ -218,7 +467,7 @@ impl TodoFile {
             .iter()
             .filter(|e| e.completed)
             .collect::<Vec<&parser::Todo>>();
-        done.sort_by(|a, b| a.completion.cmp(&b.completion));
+        done.sort_by_key(|e| e.completion);
 
         // merge the two lists
         let mut new_todos = Vec::new();
@@ -232,121 +481,128 @@ impl TodoFile {
     /// The query is a string that is searched in the `content` field of the todo items.
     /// This search happens in a case-sensitive manner and takes O(n) time in the worst case.
     pub fn search(&self, query: &str) -> Vec<parser::Todo> {
-        self.todos
-            .iter()
-            .filter(|e| e.content.contains(query))
-            .cloned()
-            .collect()
+        parallel::filter(&self.todos, |e| e.content.contains(query))
     }
 
     /// Searches for a query in the todo items using a regex.
     /// Regex can be seen as significantly faster than the `search` method.
     /// Alternatively, helper methods like `get_project` and `get_context` can be used to
     /// simplify the search.
-    pub fn regex(&self, query: &str) -> Vec<parser::Todo> {
-        let re = Regex::new(query).unwrap();
-        self.todos
-            .iter()
-            .filter(|e| re.is_match(&e.content).unwrap())
-            .cloned()
-            .collect()
+    ///
+    /// Returns a `TodoErr::RegexParseErr` error if the query isn't a valid regex or if
+    /// matching it fails.
+    pub fn regex(&self, query: &str) -> Result<Vec<parser::Todo>, TodoErr> {
+        let re = Regex::new(query).map_err(|e| TodoErr::RegexParseErr(e.to_string()))?;
+        let matches = parallel::map(&self.todos, |_, todo| {
+            re.is_match(&todo.content).map_err(|e| e.to_string())
+        });
+        let mut todos = Vec::new();
+        for (todo, matched) in self.todos.iter().zip(matches) {
+            if matched.map_err(TodoErr::RegexParseErr)? {
+                todos.push(todo.clone());
+            }
+        }
+
+        Ok(todos)
     }
 
     /// Gets all the todo items that have a specific project.
-    /// Uses a combination of regex and the `parser::Todo` struct to get the project.
+    /// A todo item matches if any of its projects is the given one.
+    /// Uses the already parsed projects of the `parser::Todo` struct.
     pub fn get_project(&self, project: &str) -> Vec<parser::Todo> {
-        self.todos
-            .iter()
-            .filter(|e| {
-                if let Ok(Some(p)) = e.parse_project() {
-                    p == project
-                } else {
-                    false
-                }
-            })
-            .cloned()
-            .collect()
+        parallel::filter(&self.todos, |e| e.projects.iter().any(|p| p == project))
     }
 
     /// Gets all the todo items that have a specific context.
     /// Works similarly to the `get_project` method.
     pub fn get_context(&self, context: &str) -> Vec<parser::Todo> {
-        self.todos
-            .iter()
-            .filter(|e| {
-                if let Ok(Some(c)) = e.parse_context() {
-                    c == context
-                } else {
-                    false
-                }
-            })
-            .cloned()
-            .collect()
+        parallel::filter(&self.todos, |e| e.contexts.iter().any(|c| c == context))
     }
 
-    /// Lists all of the projects in the todo items in a sorted and deduplicated manner.
-    pub fn list_projects(&self) -> Vec<String> {
-        let mut projects = Vec::new();
-        for todo in &self.todos {
-            if let Ok(Some(project)) = todo.parse_project() {
-                projects.push(project);
-            }
-        }
+    /// Gets all the todo items that have the given project or one of the projects below it.
+    /// With the default separator, `work` matches `+work` and `+work.backend.auth` but not
+    /// `+workshop`.
+    pub fn get_project_within(&self, project: &str) -> Vec<parser::Todo> {
+        parallel::filter(&self.todos, |e| {
+            e.projects
+                .iter()
+                .any(|p| tree::is_within(p, project, self.separator))
+        })
+    }
 
-        projects.sort();
-        projects.dedup();
+    /// Gets all the todo items that have the given context or one of the contexts below it.
+    /// Works similarly to the `get_project_within` method.
+    pub fn get_context_within(&self, context: &str) -> Vec<parser::Todo> {
+        parallel::filter(&self.todos, |e| {
+            e.contexts
+                .iter()
+                .any(|c| tree::is_within(c, context, self.separator))
+        })
+    }
 
-        projects
+    /// Returns the separator between the levels of hierarchical project and context names.
+    pub fn separator(&self) -> char {
+        self.separator
     }
 
-    /// Lists all of the contexts in the todo items in a sorted and deduplicated manner.
-    pub fn list_contexts(&self) -> Vec<String> {
-        let mut contexts = Vec::new();
-        for todo in &self.todos {
-            if let Ok(Some(context)) = todo.parse_context() {
-                contexts.push(context);
-            }
+    /// Sets the separator between the levels of hierarchical project and context names.
+    /// The separator has to be one of the `token::NAME_SEPARATORS`, the characters the lexer
+    /// joins the words of a name on, otherwise a name like `+work:backend` wouldn't be read as
+    /// a single project in the first place. Returns a `TodoErr::InvalidSeparator` error for any
+    /// other character and keeps the current separator.
+    pub fn set_separator(&mut self, separator: char) -> Result<(), TodoErr> {
+        if !token::NAME_SEPARATORS.contains(&separator) {
+            return Err(TodoErr::InvalidSeparator(separator));
         }
 
-        contexts.sort();
-        contexts.dedup();
+        self.separator = separator;
+        Ok(())
+    }
 
-        contexts
+    /// Lists the projects in the todo items as trees, split on the `separator`.
+    /// Every level of a project becomes a node, so `+work.backend` also lists `work`.
+    pub fn project_tree(&self) -> Vec<tree::Node> {
+        let projects = self.list_projects();
+        tree::build(projects.iter().map(String::as_str), self.separator)
     }
 
-    /// Lists all of the tags in the todo items in a sorted and deduplicated manner.
-    pub fn list_tags(&self) -> Vec<String> {
-        let mut tags = Vec::new();
-        for todo in &self.todos {
-            if let Ok(map) = todo.parse_tags() {
-                for (k, _) in map {
-                    tags.push(k);
-                }
-            }
-        }
+    /// Lists the contexts in the todo items as trees, split on the `separator`.
+    /// Works similarly to the `project_tree` method.
+    pub fn context_tree(&self) -> Vec<tree::Node> {
+        let contexts = self.list_contexts();
+        tree::build(contexts.iter().map(String::as_str), self.separator)
+    }
 
-        tags.sort();
-        tags.dedup();
+    /// Lists all of the projects in the todo items in a sorted and deduplicated manner.
+    pub fn list_projects(&self) -> Vec<String> {
+        parallel::names(&self.todos, |todo| todo.projects.iter().map(String::as_str))
+    }
 
-        tags
+    /// Lists all of the contexts in the todo items in a sorted and deduplicated manner.
+    pub fn list_contexts(&self) -> Vec<String> {
+        parallel::names(&self.todos, |todo| todo.contexts.iter().map(String::as_str))
+    }
+
+    /// Lists all of the tags in the todo items in a sorted and deduplicated manner.
+    pub fn list_tags(&self) -> Vec<String> {
+        parallel::names(&self.todos, |todo| todo.others.keys())
     }
 
     /// Experimental: Lists all of the hashtags in the todo items in a sorted and deduplicated
     /// manner.
     pub fn list_hashtags(&self) -> Vec<String> {
-        let mut tags = Vec::new();
-        for todo in &self.todos {
-            if let Ok(t) = todo.parse_hashtags() {
-                for tag in t {
-                    tags.push(tag);
-                }
-            }
-        }
-
-        tags.sort();
-        tags.dedup();
+        parallel::names(&self.todos, |todo| todo.view().hashtags())
+    }
 
-        tags
+    /// Returns borrowed views over the lines of the file as it was loaded.
+    /// Useful to scan or filter big files without allocating a `parser::Todo` for each line.
+    /// Changes made to the `todos` vector are not reflected until the file is saved and
+    /// reloaded.
+    pub fn views(&self) -> impl Iterator<Item = TodoView<'_>> {
+        self.content
+            .lines()
+            .filter(|l| !l.trim().is_empty() && !is_comment(l))
+            .map(|l| TodoView::with_mode(l, self.mode))
     }
 
     /// Returns a vector of all the todo items that are completed.
@@ -364,21 +620,9 @@ impl TodoFile {
     }
 
     /// Returns a vector of all the todo items that are due today.
-    /// Uses the chrono library to get the current date and compares it with the due date of the
-    /// todo item.
+    /// Works the same as the `due_on` method with the date returned by `today`.
     pub fn due_today(&self) -> Vec<parser::Todo> {
-        let today = chrono::Local::now().naive_local().date();
-        self.todos
-            .iter()
-            .filter(|e| {
-                if let Ok(Some(date)) = e.parse_due() {
-                    date == today
-                } else {
-                    false
-                }
-            })
-            .cloned()
-            .collect()
+        self.due_on(self.today())
     }
 
     /// Returns a vector of all the todo items that are due tomorrow.
@@ -397,25 +641,200 @@ impl TodoFile {
             .collect()
     }
 
+    /// Returns a vector of all the todo items that should be shown on the given date.
+    /// Todo items with a `t:` threshold date after the given date are hidden.
+    pub fn visible_on(&self, date: chrono::NaiveDate) -> Vec<parser::Todo> {
+        self.todos
+            .iter()
+            .filter(|e| e.is_visible_on(date))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns a vector of all the todo items that should be shown today.
+    /// Works the same as the `visible_on` method with the date returned by `today`.
+    pub fn visible(&self) -> Vec<parser::Todo> {
+        self.visible_on(self.today())
+    }
+
+    /// Returns the open todo items that are still hidden on the given date because of their
+    /// `t:` threshold date, sorted by the date they show up.
+    pub fn deferred(&self, date: chrono::NaiveDate) -> Vec<parser::Todo> {
+        let mut deferred = self
+            .todos
+            .iter()
+            .filter(|e| !e.completed)
+            .filter_map(|e| match e.parse_threshold() {
+                Ok(Some(t)) if t > date => Some((t, e)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        deferred.sort_by_key(|(t, _)| *t);
+
+        deferred.into_iter().map(|(_, e)| e.clone()).collect()
+    }
+
     /// Returns the file as a json of parsed todo items.
     pub fn as_json(&self) -> serde_json::Value {
         serde_json::json!(self.todos)
     }
 
     /// Parses a json value and returns a `TodoFile` struct.
-    pub fn from_json(path: &Path, json: serde_json::Value) -> Self {
-        let todos = json
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|e| serde_json::from_value(e.clone()).unwrap())
-            .collect();
+    /// Returns a `TodoErr::Json` error if the value isn't an array of todo items.
+    pub fn from_json(path: &Path, json: serde_json::Value) -> Result<Self, TodoErr> {
+        let content = format!("{}", json);
+        let todos = serde_json::from_value(json)?;
 
-        Self {
+        let mut t = Self {
             path: path.to_path_buf(),
             todos,
-            content: format!("{}", json),
+            content,
+            mode: parser::ParseMode::default(),
+            raw: Vec::new(),
+            today: None,
+            separator: DEFAULT_SEPARATOR,
+            backup: false,
+            done_path: None,
+            loaded_hash: None,
+            history: History::default(),
+        };
+        t.dedup_ids();
+
+        Ok(t)
+    }
+}
+
+/// Writes the contents to the path atomically, see `replace`, and makes the write durable with
+/// `sync_dir`.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8], backup: bool) -> io::Result<()> {
+    let written = replace(path, contents, backup)?;
+    sync_dir(&written)
+}
+
+/// Replaces the file at the path with the contents.
+/// The contents go to a temporary file next to the path, which is synced and renamed over the
+/// path, so readers only ever see the old or the new file. An existing file keeps its
+/// permissions and, with `backup`, is copied to `<path>.bak` before it is replaced. A symlink
+/// is followed, so the file it points to is replaced rather than the symlink itself.
+/// Returns the path of the file that was replaced.
+pub(crate) fn replace(path: &Path, contents: &[u8], backup: bool) -> io::Result<PathBuf> {
+    let path = match std::fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the path has no file name"))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp = dir.join(tmp_name);
+
+    let old = std::fs::metadata(&path).ok();
+    let write = || -> io::Result<()> {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(contents)?;
+        if let Some(old) = &old {
+            file.set_permissions(old.permissions())?;
         }
+        file.sync_all()?;
+
+        if backup && old.is_some() {
+            let mut bak = path.as_os_str().to_owned();
+            bak.push(".bak");
+            std::fs::copy(&path, bak)?;
+        }
+
+        std::fs::rename(&tmp, &path)
+    };
+    if let Err(e) = write() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+
+    Ok(path)
+}
+
+/// Syncs the directory of the path, which makes a rename in it durable.
+/// Does nothing on platforms that can't open a directory for this.
+pub(crate) fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
+/// Parses a single line of a file with the mode, `i` is the 0-based index of the line.
+/// Returns the kind of raw line for blank lines, comments and lines that fail to parse.
+pub(crate) fn parse_line(
+    i: usize,
+    line: &str,
+    mode: parser::ParseMode,
+) -> Result<parser::Todo, RawKind> {
+    if line.trim().is_empty() {
+        Err(RawKind::Blank)
+    } else if is_comment(line) {
+        Err(RawKind::Comment)
+    } else {
+        parser::Todo::parse_with(line, mode).map_err(|e| RawKind::Invalid(e.at_line(i + 1)))
+    }
+}
+
+/// A comment is a line starting with `#` followed by a space, or a lone `#`.
+/// This leaves todo items starting with a `#hashtag` alone.
+fn is_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line == "#" || line.starts_with("# ")
+}
+
+/// A directory for the tests that touch the disk, removed along with its contents when it is
+/// dropped, even if the test fails. The name is unique to the process and the call, so tests
+/// running in parallel never share one.
+#[cfg(test)]
+pub(crate) struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub(crate) fn new(name: &str) -> Self {
+        static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!(
+            "libdonow_test_{}_{}_{}",
+            name,
+            std::process::id(),
+            count
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("Failed to create the test directory");
+        TempDir(dir)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub(crate) fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
     }
 }
 
@@ -439,6 +858,7 @@ impl std::ops::Index<usize> for TodoFile {
 
 impl std::ops::IndexMut<usize> for TodoFile {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.history.snapshot(index, &self.todos[index]);
         &mut self.todos[index]
     }
 }
@@ -459,6 +879,22 @@ fn test_status_toggle() {
         TodoFile::from_string("x (A) 2024-08-15 2024-09-20 Hello World +hello @wow due:123\n");
     t.change_status(0);
     assert!(!t[0].completed);
+
+    let mut t = TodoFile::from_string("2024-08-01 Call mom\nx 2024-09-01 2024-08-01 Pay rent\n");
+    t.today = chrono::NaiveDate::from_ymd_opt(2024, 9, 2);
+    t.change_status(0);
+    t.change_status(1);
+    assert_eq!(
+        t.render(),
+        "x 2024-09-02 2024-08-01 Call mom\n2024-08-01 Pay rent\n"
+    );
+    for todo in &t.todos {
+        let reparsed = parser::Todo::parse(&todo.to_string()).unwrap();
+        assert_eq!(reparsed.completed, todo.completed);
+        assert_eq!(reparsed.creation, todo.creation);
+        assert_eq!(reparsed.completion, todo.completion);
+        assert_eq!(reparsed.title, todo.title);
+    }
 }
 
 #[test]
@@ -482,6 +918,22 @@ fn test_rearrange() {
     assert_eq!(rearranged[0].title, "Nice");
 }
 
+#[test]
+fn test_rearrange_by_priority() {
+    let mut t = TodoFile::from_string(
+        "2024-08-01 No priority\n(B) 2024-08-03 Second\n(A) 2024-08-05 First\n(B) 2024-08-02 Also second\n",
+    );
+    let titles = t
+        .rearrange()
+        .into_iter()
+        .map(|t| t.title)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        titles,
+        vec!["First", "Also second", "Second", "No priority"]
+    );
+}
+
 #[test]
 fn test_due_on() {
     let t = TodoFile::from_string("x (A) 2024-08-15 2024-09-20 Hello World +hello @wow due:2021-08-15\n (B) 2024-08-02 Nice +hi @wow due:2021-08-16\n");
@@ -495,3 +947,350 @@ fn test_search() {
     let search = t.search("Hello");
     assert_eq!(search[0].title, "Hello World");
 }
+
+#[test]
+fn test_save_round_trip() {
+    let content = "(B) Call mom +family  @phone due:2024-09-01 +home\nx 2024-08-15 2024-08-01 Pay rent  rent:1200\n";
+    let dir = TempDir::new("save_round_trip");
+    let path = dir.join("todo.txt");
+    let mut t = TodoFile::from_string(content);
+    t.path = path.clone();
+    t.save().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+}
+
+#[test]
+fn test_multiple_projects_contexts() {
+    let t = TodoFile::from_string("Plan +work +q3 @phone @office\nCall +home @phone\n");
+    assert_eq!(t.list_projects(), vec!["home", "q3", "work"]);
+    assert_eq!(t.list_contexts(), vec!["office", "phone"]);
+    assert_eq!(t.get_project("q3")[0].title, "Plan");
+    assert_eq!(t.get_context("office").len(), 1);
+    assert_eq!(t.get_context("phone").len(), 2);
+}
+
+#[test]
+fn test_parse_mode() {
+    let mut t = TodoFile::from_string("xylophone practice\nx 2024-09-20 2024-08-15 Done\n");
+    assert!(!t[0].completed);
+    assert_eq!(
+        t[1].completion,
+        chrono::NaiveDate::from_ymd_opt(2024, 9, 20)
+    );
+
+    t.mode = parser::ParseMode::Lenient;
+    t.load();
+    assert!(t[0].completed);
+    assert_eq!(t[1].creation, chrono::NaiveDate::from_ymd_opt(2024, 9, 20));
+}
+
+#[test]
+fn test_views() {
+    let t = TodoFile::from_string("(A) Call mom +family\n\nx Pay rent +home\n");
+    let open = t
+        .views()
+        .filter(|v| !v.is_completed())
+        .map(|v| v.line())
+        .collect::<Vec<_>>();
+    assert_eq!(open, vec!["(A) Call mom +family"]);
+    assert_eq!(t.list_tags(), Vec::<String>::new());
+}
+
+#[test]
+fn test_diagnostics() {
+    let t = TodoFile::from_string("Fine\n+project\n\n2024-13-45 Bad date\n");
+    assert_eq!(t.len(), 1);
+    let errors = t.diagnostics();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].line(), Some(2));
+    assert_eq!(errors[1].line(), Some(4));
+    assert_eq!(errors[1].span(), Some(0..10));
+}
+
+#[test]
+fn test_errors_instead_of_panics() {
+    assert!(matches!(
+        TodoFile::from_path(Path::new("/nonexistent/todo.txt")),
+        Err(TodoErr::Io(_))
+    ));
+    let t = TodoFile::from_string("Hello\n");
+    assert!(matches!(t.regex("("), Err(TodoErr::RegexParseErr(_))));
+    assert_eq!(t.regex("^Hel").unwrap().len(), 1);
+    assert!(matches!(
+        TodoFile::from_json(Path::new("todo.txt"), serde_json::json!(1)),
+        Err(TodoErr::Json(_))
+    ));
+    let json = t.as_json();
+    assert_eq!(
+        TodoFile::from_json(Path::new("todo.txt"), json)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_raw_lines_round_trip() {
+    let content =
+        "# Work\n(A) Call mom +family\n\n+project\n#home Fix sink\n2024-13-45 Bad date\n\n";
+    let t = TodoFile::from_string(content);
+    assert_eq!(t.len(), 2);
+    assert_eq!(t.raw.len(), 5);
+    assert!(matches!(t.raw[0].kind, RawKind::Comment));
+    assert!(matches!(t.raw[2].kind, RawKind::Invalid(_)));
+    assert_eq!(t.render(), content);
+}
+
+#[test]
+fn test_raw_lines_follow_removal() {
+    let mut t = TodoFile::from_string("First\n# about second\nSecond\nThird\n");
+    t.remove(0);
+    assert_eq!(t.render(), "# about second\nSecond\nThird\n");
+    t.add(parser::Todo::parse("Fourth").unwrap());
+    assert_eq!(t.render(), "# about second\nSecond\nThird\nFourth\n");
+}
+
+#[test]
+fn test_complete_reopen() {
+    let mut t = TodoFile::from_string("(B) 2024-08-02 Nice +hi @wow\n");
+    let next = t
+        .complete(0, chrono::NaiveDate::from_ymd_opt(2024, 8, 20).unwrap())
+        .unwrap();
+    assert_eq!(next, None);
+    assert_eq!(t.render(), "x 2024-08-20 2024-08-02 Nice +hi @wow pri:B\n");
+    assert_eq!(t.completed().len(), 1);
+    t.reopen(0);
+    assert_eq!(t.render(), "(B) 2024-08-02 Nice +hi @wow\n");
+}
+
+#[test]
+fn test_threshold() {
+    let t = TodoFile::from_string(
+        "Now\nLater t:2024-10-05\nSoon t:2024-09-10\nx 2024-08-01 2024-08-01 Done t:2024-12-01\n",
+    );
+    let today = chrono::NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+    let visible = t.visible_on(today);
+    assert_eq!(visible.len(), 1);
+    assert_eq!(visible[0].title, "Now");
+
+    let deferred = t.deferred(today);
+    assert_eq!(deferred.len(), 2);
+    assert_eq!(deferred[0].title, "Soon");
+    assert_eq!(deferred[1].title, "Later");
+
+    let later = chrono::NaiveDate::from_ymd_opt(2024, 9, 10).unwrap();
+    assert_eq!(t.visible_on(later).len(), 2);
+}
+
+#[test]
+fn test_views_use_today() {
+    let mut t = TodoFile::from_string(
+        "Call mom due:2024-09-01
+Pay rent t:2024-09-10
+",
+    );
+    t.today = chrono::NaiveDate::from_ymd_opt(2024, 9, 1);
+    assert_eq!(t.due_today().len(), 1);
+    assert_eq!(t.visible().len(), 1);
+    t.today = chrono::NaiveDate::from_ymd_opt(2024, 9, 10);
+    assert!(t.due_today().is_empty());
+    assert_eq!(t.visible().len(), 2);
+}
+
+#[test]
+fn test_complete_recurring() {
+    let mut t = TodoFile::from_string("2024-08-01 Water plants rec:1w due:2024-09-01\n");
+    let on = chrono::NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+    assert_eq!(t.complete(0, on).unwrap(), Some(1));
+    assert_eq!(
+        t.render(),
+        "x 2024-09-02 2024-08-01 Water plants rec:1w due:2024-09-01\n2024-09-02 Water plants rec:1w due:2024-09-09\n"
+    );
+    assert_eq!(t.complete(0, on).unwrap(), None);
+    assert_eq!(t.len(), 2);
+}
+
+#[test]
+fn test_resolve_relative_dates() {
+    let mut t = TodoFile::from_string("Call mom due:tomorrow\n");
+    t.today = chrono::NaiveDate::from_ymd_opt(2024, 9, 2);
+    t.add(parser::Todo::parse("Pay rent due:+1w").unwrap());
+    assert_eq!(t[1].to_string(), "Pay rent due:2024-09-09");
+    assert_eq!(t.resolve_dates(), 1);
+    assert_eq!(
+        t.render(),
+        "Call mom due:2024-09-03\nPay rent due:2024-09-09\n"
+    );
+}
+
+#[test]
+fn test_hierarchical_projects() {
+    let mut t = TodoFile::from_string(
+        "Fix login +work.backend.auth\nRestyle +work.frontend @desk/home\nSand +workshop\nNap @desk\n",
+    );
+    assert_eq!(t.get_project("work").len(), 0);
+    let titles = t
+        .get_project_within("work")
+        .into_iter()
+        .map(|t| t.title)
+        .collect::<Vec<_>>();
+    assert_eq!(titles, vec!["Fix login", "Restyle"]);
+    assert_eq!(t.get_project_within("work.backend").len(), 1);
+    assert_eq!(t.get_context_within("desk").len(), 1);
+
+    let tree = t.project_tree();
+    assert_eq!(tree.len(), 2);
+    assert_eq!(
+        tree[0].to_string(),
+        "work\n  backend\n    auth\n  frontend\n"
+    );
+
+    t.set_separator('/').unwrap();
+    assert_eq!(t.get_context_within("desk").len(), 2);
+    assert_eq!(t.context_tree()[0].children[0].path, "desk/home");
+    assert!(matches!(
+        t.set_separator(':'),
+        Err(TodoErr::InvalidSeparator(':'))
+    ));
+    assert_eq!(t.separator(), '/');
+}
+
+#[test]
+fn test_queries_see_edits() {
+    let mut t = TodoFile::from_string("Call mom +family due:2024-09-01\n");
+    t.today = chrono::NaiveDate::from_ymd_opt(2024, 9, 2);
+    t[0].set_title("Call dad").unwrap();
+    t[0].set_due(chrono::NaiveDate::from_ymd_opt(2024, 9, 2));
+    t.change_status(0);
+    assert_eq!(t.search("Call dad").len(), 1);
+    assert_eq!(t.regex("^x ").unwrap().len(), 1);
+    assert_eq!(
+        t.due_on(chrono::NaiveDate::from_ymd_opt(2024, 9, 2).unwrap())
+            .len(),
+        1
+    );
+
+    let mut todo = t.get(0).unwrap();
+    todo.projects.clear();
+    t.update(0, todo);
+    assert_eq!(t[0].content, "x 2024-09-02 Call dad due:2024-09-02");
+}
+
+#[test]
+fn test_save_is_atomic() {
+    let dir = TempDir::new("save_is_atomic");
+    let path = dir.join("todo.txt");
+    std::fs::write(&path, "Old\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    }
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    t.backup = true;
+    t.add(parser::Todo::parse("New").unwrap());
+    t.save().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "Old\nNew\n");
+    assert_eq!(
+        std::fs::read_to_string(dir.join("todo.txt.bak")).unwrap(),
+        "Old\n"
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+    t.path = dir.join("missing").join("todo.txt");
+    assert!(t.save().is_err());
+}
+
+#[test]
+fn test_save_detects_external_changes() {
+    let dir = TempDir::new("save_detects_external_changes");
+    let path = dir.join("todo.txt");
+    std::fs::write(&path, "Call mom\nPay rent\n").unwrap();
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    t.today = chrono::NaiveDate::from_ymd_opt(2024, 9, 2);
+    t.change_status(0);
+    assert!(!t.is_modified_on_disk().unwrap());
+    std::fs::write(&path, "Call mom\nPay rent +home\nWater plants\n").unwrap();
+    assert!(t.is_modified_on_disk().unwrap());
+
+    let e = t.save().unwrap_err();
+    assert!(merge::is_modified_on_disk(&e));
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "Call mom\nPay rent +home\nWater plants\n"
+    );
+
+    let report = t.save_merged().unwrap();
+    assert!(report.is_clean());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "x 2024-09-02 Call mom\nPay rent +home\nWater plants\n"
+    );
+    assert_eq!(t.len(), 3);
+
+    t[1].set_title("Pay the rent").unwrap();
+    std::fs::write(
+        &path,
+        "x 2024-09-02 Call mom\nPay rent +flat\nWater plants\n",
+    )
+    .unwrap();
+    let report = t.save_merged().unwrap();
+    assert_eq!(report.conflicts.len(), 1);
+    assert_eq!(report.conflicts[0].line, 2);
+    assert_eq!(report.conflicts[0].theirs, vec!["Pay rent +flat"]);
+    assert_eq!(t[1].content, "Pay the rent +home");
+}
+
+#[test]
+fn test_new() {
+    let dir = TempDir::new("new");
+    let path = dir.join("todo.txt");
+    let t = TodoFile::new(path.to_str().unwrap());
+    assert!(t.is_empty());
+    assert!(!t.is_modified_on_disk().unwrap());
+
+    std::fs::write(&path, "Call mom\n# chores\nPay rent\n").unwrap();
+    let t = TodoFile::new(path.to_str().unwrap());
+    assert_eq!(t.len(), 2);
+    assert_eq!(t.raw.len(), 1);
+    assert_eq!(t.render(), t.content);
+}
+
+#[test]
+fn test_lowercase_priority_is_title() {
+    let t = TodoFile::from_string("(a) Call mom\n");
+    assert_eq!(t.len(), 1);
+    assert!(t.raw.is_empty());
+    assert_eq!(t[0].title, "(a) Call mom");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_save_through_symlink() {
+    let dir = TempDir::new("save_through_symlink");
+    let target = dir.join("real.txt");
+    let link = dir.join("todo.txt");
+    std::fs::write(&target, "Call mom\n").unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let mut t = TodoFile::from_path(&link).unwrap();
+    t.add(parser::Todo::parse("Pay rent").unwrap());
+    t.save().unwrap();
+    assert!(std::fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(
+        std::fs::read_to_string(&target).unwrap(),
+        "Call mom\nPay rent\n"
+    );
+    assert!(!t.is_modified_on_disk().unwrap());
+}
@@ -0,0 +1,494 @@
+//! # History
+//!
+//! Undo and redo for the changes made to a `TodoFile`.
+//! Every change made through `add`, `remove`, `update`, `change_status`, `complete`, `reopen`
+//! and the `[]` operator is recorded in the `History` of the file as a list of `Command`s
+//! that can be reverted with `TodoFile::undo` and applied again with `TodoFile::redo`.
+//! Changes made to the `todos` vector directly aren't recorded.
+//!
+//! The history can be kept next to the file as `<path>.history`, so that it survives a
+//! restart of the application. Set `History::persist` to write it on every `save`, and call
+//! `TodoFile::load_history` after reading the file.
+//!
+//! ```rust
+//! use libdonow::{file::TodoFile, parser::Todo};
+//!
+//! let mut file = TodoFile::from_string("Call mom\n");
+//! file.add(Todo::parse("Pay rent").unwrap());
+//! file[0].title = "Call dad".to_string();
+//! assert!(file.undo());
+//! assert_eq!(file[0].title, "Call mom");
+//! assert!(file.undo());
+//! assert_eq!(file.len(), 1);
+//! assert!(file.redo());
+//! assert_eq!(file.len(), 2);
+//! ```
+
+use std::{io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{file::TodoFile, merge, parser::Todo};
+
+/// The number of changes a `History` keeps by default.
+pub const DEFAULT_DEPTH: usize = 100;
+
+/// A single change to the todo items of a `TodoFile` that can be reverted.
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// A todo item was inserted at the index.
+    Insert {
+        /// The index of the new todo item.
+        index: usize,
+        /// The new todo item.
+        todo: Todo,
+        /// The positions of the raw lines after the insertion, to put them back in place.
+        positions: Vec<usize>,
+    },
+    /// The todo item at the index was removed.
+    Remove {
+        /// The index of the removed todo item.
+        index: usize,
+        /// The removed todo item.
+        todo: Todo,
+        /// The positions of the raw lines before the removal, to put them back in place.
+        positions: Vec<usize>,
+    },
+    /// The todo item at the index was replaced.
+    Replace {
+        /// The index of the todo item.
+        index: usize,
+        /// The todo item before the change.
+        old: Todo,
+        /// The todo item after the change.
+        new: Todo,
+    },
+}
+
+/// The undo and redo stacks of a `TodoFile`.
+/// Each entry is the list of commands of one change, `TodoFile::complete` for example inserts
+/// the next occurrence of a recurring todo item in the same change that completes it.
+#[derive(Debug, Clone)]
+pub struct History {
+    /// The number of changes that can be undone. Older changes are forgotten, 0 turns the
+    /// history off.
+    pub depth: usize,
+    /// Whether `TodoFile::save` writes the history to `TodoFile::history_path` as well.
+    pub persist: bool,
+    undo: Vec<Vec<Command>>,
+    redo: Vec<Vec<Command>>,
+    /// The todo items handed out by the `[]` operator, as they were before.
+    pending: Vec<(usize, Todo)>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            depth: DEFAULT_DEPTH,
+            persist: false,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl History {
+    /// Returns whether there is a change to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || !self.pending.is_empty()
+    }
+
+    /// Returns whether there is a change to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forgets every change.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending.clear();
+    }
+
+    /// Remembers the todo item before the `[]` operator hands it out for a change.
+    pub(crate) fn snapshot(&mut self, index: usize, todo: &Todo) {
+        if self.depth > 0 && !self.pending.iter().any(|(i, _)| *i == index) {
+            self.pending.push((index, todo.clone()));
+        }
+    }
+
+    fn push(&mut self, commands: Vec<Command>) {
+        if self.depth == 0 || commands.is_empty() {
+            return;
+        }
+
+        self.undo.push(commands);
+        self.redo.clear();
+        if self.undo.len() > self.depth {
+            let over = self.undo.len() - self.depth;
+            self.undo.drain(..over);
+        }
+    }
+}
+
+impl TodoFile {
+    /// Records a change in the history.
+    /// `flush_edits` has to be called before the change is made.
+    pub(crate) fn record(&mut self, commands: Vec<Command>) {
+        self.history.push(commands);
+    }
+
+    /// Records the changes made through the `[]` operator since the last change.
+    pub(crate) fn flush_edits(&mut self) {
+        let commands = std::mem::take(&mut self.history.pending)
+            .into_iter()
+            .filter(|(index, old)| {
+                self.todos
+                    .get(*index)
+                    .is_some_and(|new| new.to_string() != old.to_string())
+            })
+            .map(|(index, old)| Command::Replace {
+                index,
+                old,
+                new: self.todos[index].clone(),
+            })
+            .collect();
+        self.record(commands);
+    }
+
+    /// Changes the todo item at the index and records the change.
+    pub(crate) fn modify(&mut self, index: usize, f: impl FnOnce(&mut Todo)) {
+        self.flush_edits();
+        let old = self.todos[index].clone();
+        f(&mut self.todos[index]);
+        let new = self.todos[index].clone();
+        self.record(vec![Command::Replace { index, old, new }]);
+    }
+
+    /// Reverts the last change. Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        self.flush_edits();
+        let Some(commands) = self.history.undo.pop() else {
+            return false;
+        };
+
+        for c in commands.iter().rev() {
+            match c {
+                Command::Insert { index, .. } => self.take(*index),
+                Command::Remove {
+                    index,
+                    todo,
+                    positions,
+                } => self.put(*index, todo, positions),
+                Command::Replace { index, old, .. } => self.todos[*index] = old.clone(),
+            }
+        }
+        self.history.redo.push(commands);
+
+        true
+    }
+
+    /// Applies the last change that was undone again. Returns `false` if there is nothing to
+    /// redo. Any new change forgets the changes that can be redone.
+    pub fn redo(&mut self) -> bool {
+        self.flush_edits();
+        let Some(commands) = self.history.redo.pop() else {
+            return false;
+        };
+
+        for c in &commands {
+            match c {
+                Command::Insert {
+                    index,
+                    todo,
+                    positions,
+                } => self.put(*index, todo, positions),
+                Command::Remove { index, .. } => self.take(*index),
+                Command::Replace { index, new, .. } => self.todos[*index] = new.clone(),
+            }
+        }
+        self.history.undo.push(commands);
+
+        true
+    }
+
+    /// Removes the todo item at the index without recording it.
+    /// The raw lines after it move up with the rest of the file.
+    pub(crate) fn take(&mut self, index: usize) {
+        self.todos.remove(index);
+        for r in self.raw.iter_mut().filter(|r| r.position > index) {
+            r.position -= 1;
+        }
+    }
+
+    /// Inserts the todo item at the index without recording it, moving the raw lines back to
+    /// the positions they had with the todo item in the file. This undoes `take`.
+    fn put(&mut self, index: usize, todo: &Todo, positions: &[usize]) {
+        self.todos.insert(index, todo.clone());
+        for (r, p) in self.raw.iter_mut().zip(positions) {
+            r.position = *p;
+        }
+    }
+
+    /// Returns the positions of the raw lines, see `put`.
+    pub(crate) fn raw_positions(&self) -> Vec<usize> {
+        self.raw.iter().map(|r| r.position).collect()
+    }
+
+    /// Returns the path the history is kept at, `<path>.history`.
+    pub fn history_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".history");
+        PathBuf::from(path)
+    }
+
+    /// Writes the history to `history_path`.
+    /// The history remembers the todo items it belongs to, `load_history` ignores it once the
+    /// file was changed without it.
+    pub fn save_history(&mut self) -> io::Result<()> {
+        self.flush_edits();
+        let stored = |entries: &[Vec<Command>]| {
+            entries
+                .iter()
+                .map(|e| e.iter().map(Stored::from).collect())
+                .collect()
+        };
+        let journal = Journal {
+            hash: merge::hash(&self.render()),
+            undo: stored(&self.history.undo),
+            redo: stored(&self.history.redo),
+        };
+
+        let json = serde_json::to_string(&journal)?;
+        crate::file::write_atomic(&self.history_path(), json.as_bytes(), false)
+    }
+
+    /// Reads the history written by `save_history` back.
+    /// Returns `false` and keeps the current history if there is no history at
+    /// `history_path`, or if it belongs to a different version of the file.
+    pub fn load_history(&mut self) -> io::Result<bool> {
+        let json = match std::fs::read_to_string(self.history_path()) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let journal: Journal = serde_json::from_str(&json)?;
+        if journal.hash != merge::hash(&self.render()) {
+            return Ok(false);
+        }
+
+        let entries = |stored: Vec<Vec<Stored>>| -> io::Result<Vec<Vec<Command>>> {
+            stored
+                .into_iter()
+                .map(|e| e.into_iter().map(|s| s.command(self)).collect())
+                .collect()
+        };
+        let undo = entries(journal.undo)?;
+        let redo = entries(journal.redo)?;
+        self.history.undo = undo;
+        self.history.redo = redo;
+        self.history.pending.clear();
+
+        Ok(true)
+    }
+}
+
+/// The history as it is written to disk.
+#[derive(Serialize, Deserialize)]
+struct Journal {
+    /// The hash of the file the history belongs to, as `render` writes it.
+    hash: u64,
+    undo: Vec<Vec<Stored>>,
+    redo: Vec<Vec<Stored>>,
+}
+
+/// A `Command` as it is written to disk, with the todo items as lines.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Stored {
+    Insert {
+        index: usize,
+        todo: String,
+        #[serde(default)]
+        positions: Vec<usize>,
+    },
+    Remove {
+        index: usize,
+        todo: String,
+        positions: Vec<usize>,
+    },
+    Replace {
+        index: usize,
+        old: String,
+        new: String,
+    },
+}
+
+impl From<&Command> for Stored {
+    fn from(c: &Command) -> Self {
+        match c {
+            Command::Insert {
+                index,
+                todo,
+                positions,
+            } => Stored::Insert {
+                index: *index,
+                todo: todo.to_string(),
+                positions: positions.clone(),
+            },
+            Command::Remove {
+                index,
+                todo,
+                positions,
+            } => Stored::Remove {
+                index: *index,
+                todo: todo.to_string(),
+                positions: positions.clone(),
+            },
+            Command::Replace { index, old, new } => Stored::Replace {
+                index: *index,
+                old: old.to_string(),
+                new: new.to_string(),
+            },
+        }
+    }
+}
+
+impl Stored {
+    /// Parses the lines back with the mode of the file.
+    fn command(self, file: &TodoFile) -> io::Result<Command> {
+        let parse = |line: &str| {
+            Todo::parse_with(line, file.mode)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+        };
+        Ok(match self {
+            Stored::Insert {
+                index,
+                todo,
+                positions,
+            } => Command::Insert {
+                index,
+                todo: parse(&todo)?,
+                positions,
+            },
+            Stored::Remove {
+                index,
+                todo,
+                positions,
+            } => Command::Remove {
+                index,
+                todo: parse(&todo)?,
+                positions,
+            },
+            Stored::Replace { index, old, new } => Command::Replace {
+                index,
+                old: parse(&old)?,
+                new: parse(&new)?,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_undo_redo() {
+    let mut t = TodoFile::from_string("Call mom\n# chores\nPay rent\nWater plants\n");
+    t.today = chrono::NaiveDate::from_ymd_opt(2024, 9, 2);
+    t.remove(1);
+    t.change_status(0);
+    t[1].title = "Water the plants".to_string();
+    t.add(Todo::parse("Buy milk").unwrap());
+    assert_eq!(
+        t.render(),
+        "x 2024-09-02 Call mom\n# chores\nWater the plants\nBuy milk\n"
+    );
+
+    while t.undo() {}
+    assert_eq!(t.render(), "Call mom\n# chores\nPay rent\nWater plants\n");
+    assert!(!t.history.can_undo());
+
+    assert!(t.redo());
+    assert!(t.redo());
+    assert_eq!(
+        t.render(),
+        "x 2024-09-02 Call mom\n# chores\nWater plants\n"
+    );
+
+    // a new change forgets what could be redone
+    t.update(0, Todo::parse("Call dad").unwrap());
+    assert!(!t.redo());
+    assert!(t.undo());
+    assert_eq!(t[0].to_string(), "x 2024-09-02 Call mom");
+}
+
+#[test]
+fn test_undo_redo_keeps_raw_lines() {
+    let content = "# top\nCall mom\n# middle\nPay rent\n# bottom\n";
+    let mut t = TodoFile::from_string(content);
+    t.remove(1);
+    t.remove(0);
+    t.add(Todo::parse("Buy milk").unwrap());
+    t[0].title = "Buy oat milk".to_string();
+    let edited = "# top\n# middle\n# bottom\nBuy oat milk\n";
+    assert_eq!(t.render(), edited);
+
+    while t.undo() {}
+    assert_eq!(t.render(), content);
+    while t.redo() {}
+    assert_eq!(t.render(), edited);
+    assert!(t.undo());
+    assert!(t.undo());
+    assert_eq!(t.render(), "# top\n# middle\n# bottom\n");
+    assert!(t.undo());
+    assert_eq!(t.render(), "# top\nCall mom\n# middle\n# bottom\n");
+    assert!(t.redo());
+    assert!(t.redo());
+    assert_eq!(t.render(), "# top\n# middle\n# bottom\nBuy milk\n");
+}
+
+#[test]
+fn test_history_depth() {
+    let mut t = TodoFile::from_string("");
+    t.history.depth = 2;
+    for title in ["A", "B", "C"] {
+        t.add(Todo::parse(title).unwrap());
+    }
+    assert!(t.undo());
+    assert!(t.undo());
+    assert!(!t.undo());
+    assert_eq!(t.len(), 1);
+}
+
+#[test]
+fn test_complete_undo() {
+    let mut t = TodoFile::from_string("Water plants rec:1w due:2024-09-01\n");
+    let on = chrono::NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+    assert_eq!(t.complete(0, on).unwrap(), Some(1));
+    assert!(t.undo());
+    assert_eq!(t.len(), 1);
+    assert!(!t[0].completed);
+}
+
+#[test]
+fn test_persist_history() {
+    let dir = crate::file::TempDir::new("history");
+    let path = dir.join("todo.txt");
+    std::fs::write(&path, "Call mom\n").unwrap();
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    t.history.persist = true;
+    t.add(Todo::parse("(A) Pay rent +home").unwrap());
+    t.save().unwrap();
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    assert!(t.load_history().unwrap());
+    assert!(t.undo());
+    assert_eq!(t.render(), "Call mom\n");
+
+    // a history that doesn't belong to the file is ignored
+    std::fs::write(&path, "Something else\n").unwrap();
+    let mut t = TodoFile::from_path(&path).unwrap();
+    assert!(!t.load_history().unwrap());
+    assert!(!t.undo());
+}
@@ -0,0 +1,274 @@
+//! # Ids
+//!
+//! Stable identifiers for todo items.
+//! The index of a todo item in `TodoFile::todos` changes whenever a todo item before it is
+//! removed or the todo items are reordered. A `TodoId` doesn't: every todo item gets one when
+//! it is created, it moves along with the todo item and copies of it, and `TodoFile::update`,
+//! `TodoFile::load`, `TodoFile::reload` and `TodoFile::merge_disk` keep it for the todo items
+//! they replace.
+//!
+//! The ids are only unique for as long as the program runs, unless they are written to the
+//! file as `id:` tags with `TodoFile::persist_ids`. A todo item with an `id:` tag holding a
+//! number up to `MAX_ID` always has that id, larger numbers are plain tags.
+//!
+//! ```rust
+//! use libdonow::file::TodoFile;
+//!
+//! let mut file = TodoFile::from_string("Call mom\nPay rent id:7\n");
+//! let id = file[1].id();
+//! assert_eq!(id.to_string(), "7");
+//! file.remove(0);
+//! assert_eq!(file[id].title, "Pay rent");
+//! assert!(file.change_status_by_id(id));
+//! ```
+
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    file::TodoFile,
+    merge,
+    parser::{Todo, TodoErr},
+};
+
+/// The key of the tag ids are persisted in.
+pub const ID_TAG: &str = "id";
+
+/// The largest id an `id:` tag can hold.
+/// The ids handed out are above every id seen in a tag, so this leaves room for them.
+pub const MAX_ID: u64 = u64::MAX / 2;
+
+/// The identifier of a todo item, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TodoId(pub u64);
+
+impl Display for TodoId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for TodoId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(TodoId)
+    }
+}
+
+/// The next id handed out, above every id seen in an `id:` tag so far.
+static NEXT: AtomicU64 = AtomicU64::new(1);
+
+/// Returns a new id.
+pub(crate) fn next() -> TodoId {
+    TodoId(NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Makes sure that the ids handed out from now on don't clash with the id of an `id:` tag.
+pub(crate) fn seen(id: TodoId) {
+    NEXT.fetch_max(id.0.saturating_add(1), Ordering::Relaxed);
+}
+
+/// Gives the todo items read from a file the ids of the todo items they replace.
+/// The lines are matched like `TodoFile::reload` does: a line that stayed the same keeps its
+/// id, and the changed lines between two of them take over the ids of the old ones in order.
+/// The lines left over are new and get new ids, in the order of the lines.
+pub(crate) fn carry_over(old: &[Todo], new: &mut [Todo]) {
+    let old_lines = old.iter().map(Todo::to_string).collect::<Vec<_>>();
+    let old_lines = old_lines.iter().map(String::as_str).collect::<Vec<_>>();
+    let new_lines = new.iter().map(|t| t.content.clone()).collect::<Vec<_>>();
+    let new_lines = new_lines.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let (mut o, mut n) = (0, 0);
+    let pairs = merge::matching(&old_lines, &new_lines);
+    for &(po, pn) in pairs.iter().chain([(old.len(), new.len())].iter()) {
+        let changed = (po - o).min(pn - n);
+        for (todo, old) in new[n..n + changed].iter_mut().zip(&old[o..]) {
+            todo.keep_id(old);
+        }
+        for todo in &mut new[n + changed..pn] {
+            if !todo.has_id_tag() {
+                todo.renumber();
+            }
+        }
+        if let (Some(todo), Some(old)) = (new.get_mut(pn), old.get(po)) {
+            todo.keep_id(old);
+        }
+        (o, n) = (po + 1, pn + 1);
+    }
+}
+
+impl TodoFile {
+    /// Returns the index of the todo item with the id.
+    pub fn position(&self, id: TodoId) -> Option<usize> {
+        self.todos.iter().position(|t| t.id() == id)
+    }
+
+    /// Gets the todo item with the id. Returns None if there is none.
+    pub fn get_by_id(&self, id: TodoId) -> Option<Todo> {
+        self.position(id).map(|i| self.todos[i].clone())
+    }
+
+    /// Removes the todo item with the id, see `remove`.
+    /// Returns whether there was a todo item with the id.
+    pub fn remove_by_id(&mut self, id: TodoId) -> bool {
+        self.position(id).map(|i| self.remove(i)).is_some()
+    }
+
+    /// Updates the todo item with the id, see `update`.
+    /// Returns whether there was a todo item with the id.
+    pub fn update_by_id(&mut self, id: TodoId, todo: Todo) -> bool {
+        self.position(id).map(|i| self.update(i, todo)).is_some()
+    }
+
+    /// Changes the status of the todo item with the id, see `change_status`.
+    /// Returns whether there was a todo item with the id.
+    pub fn change_status_by_id(&mut self, id: TodoId) -> bool {
+        self.position(id).map(|i| self.change_status(i)).is_some()
+    }
+
+    /// Completes the todo item with the id on the given date, see `complete`.
+    /// Returns the id of the next occurrence of a recurring todo item. Does nothing if there is
+    /// no todo item with the id.
+    pub fn complete_by_id(
+        &mut self,
+        id: TodoId,
+        on: chrono::NaiveDate,
+    ) -> Result<Option<TodoId>, TodoErr> {
+        match self.position(id) {
+            Some(i) => Ok(self.complete(i, on)?.map(|next| self.todos[next].id())),
+            None => Ok(None),
+        }
+    }
+
+    /// Reopens the todo item with the id, see `reopen`.
+    /// Returns whether there was a todo item with the id.
+    pub fn reopen_by_id(&mut self, id: TodoId) -> bool {
+        self.position(id).map(|i| self.reopen(i)).is_some()
+    }
+
+    /// Writes the id of every todo item without an `id:` tag into one, so that the ids stay
+    /// the same after the file is read again. Returns the number of todo items that changed.
+    pub fn persist_ids(&mut self) -> usize {
+        let mut count = 0;
+        for i in 0..self.todos.len() {
+            if !self.todos[i].has_id_tag() {
+                self.modify(i, |t| t.persist_id());
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    /// Gives the todo items that share an id with a todo item before them a new one.
+    /// This is done whenever the file is read, so the ids in the file are unique.
+    pub(crate) fn dedup_ids(&mut self) {
+        let mut ids = HashSet::new();
+        for todo in &mut self.todos {
+            seen(todo.id());
+            if !ids.insert(todo.id()) {
+                todo.renumber();
+                ids.insert(todo.id());
+            }
+        }
+    }
+}
+
+impl std::ops::Index<TodoId> for TodoFile {
+    type Output = Todo;
+
+    /// Panics if there is no todo item with the id.
+    fn index(&self, id: TodoId) -> &Self::Output {
+        let i = self.position(id).expect("no todo item with the id");
+        &self.todos[i]
+    }
+}
+
+impl std::ops::IndexMut<TodoId> for TodoFile {
+    /// Panics if there is no todo item with the id.
+    fn index_mut(&mut self, id: TodoId) -> &mut Self::Output {
+        let i = self.position(id).expect("no todo item with the id");
+        &mut self[i]
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_ids() {
+    let mut t = TodoFile::from_string("Call mom\nPay rent\nWater plants rec:1w\n");
+    let ids = t.todos.iter().map(Todo::id).collect::<Vec<_>>();
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 3);
+
+    t.remove(0);
+    assert_eq!(t.position(ids[2]), Some(1));
+    let sorted = t.rearrange();
+    assert_eq!(sorted[1].id(), ids[2]);
+
+    assert!(t.update_by_id(ids[1], Todo::parse("Pay the rent").unwrap()));
+    assert_eq!(t[ids[1]].title, "Pay the rent");
+    let on = chrono::NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+    let next = t.complete_by_id(ids[2], on).unwrap().unwrap();
+    assert_ne!(next, ids[2]);
+    assert!(!t[next].completed);
+
+    assert!(!t.remove_by_id(ids[0]));
+    assert!(t.undo());
+    assert!(t.get_by_id(next).is_none());
+}
+
+#[test]
+fn test_persist_ids() {
+    let mut t = TodoFile::from_string("Call mom id:3\nPay rent id:3\nWater plants\n");
+    assert_eq!(t[0].id(), TodoId(3));
+    assert_ne!(t[1].id(), TodoId(3));
+    assert_eq!(t.persist_ids(), 1);
+
+    let ids = t.todos.iter().map(Todo::id).collect::<Vec<_>>();
+    let again = TodoFile::from_string(&t.render());
+    assert_eq!(again.todos.iter().map(Todo::id).collect::<Vec<_>>(), ids);
+}
+
+#[test]
+fn test_ids_survive_reading_again() {
+    let mut t = TodoFile::from_string("Call mom\n# chores\nPay rent\nWater plants\n");
+    let ids = t.todos.iter().map(Todo::id).collect::<Vec<_>>();
+    t.content = "Buy milk\nCall mom\nx Pay rent\nWater plants\n".to_string();
+    t.load();
+    assert!(t.position(t[0].id()).is_some());
+    assert!(!ids.contains(&t[0].id()));
+    assert_eq!(t.todos[1..].iter().map(Todo::id).collect::<Vec<_>>(), ids);
+
+    let dir = crate::file::TempDir::new("ids_survive_reading_again");
+    let path = dir.join("todo.txt");
+    std::fs::write(&path, "Call mom\nPay rent\n").unwrap();
+    let mut t = TodoFile::from_path(&path).unwrap();
+    let ids = t.todos.iter().map(Todo::id).collect::<Vec<_>>();
+    t.today = chrono::NaiveDate::from_ymd_opt(2024, 9, 2);
+    t.change_status(1);
+    std::fs::write(&path, "Call mom +family\nPay rent\nWater plants\n").unwrap();
+    assert!(t.save_merged().unwrap().is_clean());
+    assert_eq!(t.position(ids[0]), Some(0));
+    assert_eq!(
+        t.get_by_id(ids[1]).unwrap().content,
+        "x 2024-09-02 Pay rent"
+    );
+    assert!(!ids.contains(&t[2].id()));
+}
+
+#[test]
+fn test_id_tag_out_of_range() {
+    let t = TodoFile::from_string(&format!(
+        "Call mom id:{}\nPay rent id:{}\n",
+        u64::MAX,
+        MAX_ID + 1
+    ));
+    assert!(!t[0].has_id_tag());
+    assert!(!t[1].has_id_tag());
+    assert_ne!(t[0].id(), t[1].id());
+    assert!(next() < next());
+}
@@ -19,8 +19,28 @@
 //! The TodoFile struct also has various implementations and methods to feel like a Vec<Todo> struct, but with some extra features.
 //!
 //! The library also has powerful features to work with a single todo item.
-//! Each todo item is parsed using some fancy regex features and is stored in a struct called `Todo`.
+//! Each todo item is parsed using a single pass lexer and is stored in a struct called `Todo`.
+//! For read only scans of big files, the borrowed `view::TodoView` gives the same information
+//! without allocating.
 //! the `Todo` struct follows a only what's needed approach so you have various functions and utilities to retrieve only what is necessary
 //! without having to parse the entire todo item.
+pub mod archive;
+pub mod builder;
+pub mod dates;
+pub mod event;
 pub mod file;
+pub mod history;
+pub mod id;
+pub mod merge;
+mod parallel;
 pub mod parser;
+pub mod priority;
+pub mod query;
+pub mod recur;
+pub mod stream;
+pub mod tags;
+pub mod token;
+pub mod tree;
+pub mod view;
+#[cfg(feature = "watch")]
+pub mod watch;
@@ -0,0 +1,420 @@
+//! # Merge
+//!
+//! Support for todo.txt files that are edited by more than one program at a time.
+//! `TodoFile` remembers a hash of the file as it was read, and `TodoFile::save` refuses to
+//! overwrite the file when another program changed it in the meantime. The changes can then
+//! be combined with `TodoFile::merge_disk`, a three-way line merge between the file as it was
+//! read, the todo items in memory and the file on disk.
+//!
+//! Lines changed on only one side are taken from that side. Lines changed differently on both
+//! sides are a conflict: the version in memory is kept and the other versions are reported in
+//! a `MergeReport`, so nothing is lost silently.
+//!
+//! ```rust
+//! use libdonow::merge::merge;
+//!
+//! let base = "Call mom\nPay rent\n";
+//! let ours = "x Call mom\nPay rent\n";
+//! let theirs = "Call mom\nPay rent\nWater plants\n";
+//! let (merged, report) = merge(base, ours, theirs);
+//! assert_eq!(merged, "x Call mom\nPay rent\nWater plants\n");
+//! assert!(report.is_clean());
+//! ```
+
+use std::{fmt::Display, io, ops::Range, path::PathBuf};
+
+use crate::file::TodoFile;
+
+/// The error `TodoFile::save` fails with when the file was changed by another program since it
+/// was read. It is wrapped in an `io::Error` and can be told apart with `is_modified_on_disk`.
+#[derive(Debug)]
+pub struct ModifiedOnDisk {
+    /// The path of the file.
+    pub path: PathBuf,
+}
+
+impl Display for ModifiedOnDisk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} was modified on disk since it was read",
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for ModifiedOnDisk {}
+
+/// Returns whether the error is a `ModifiedOnDisk` error from `TodoFile::save`.
+pub fn is_modified_on_disk(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<ModifiedOnDisk>())
+}
+
+/// A part of the file that was changed differently in memory and on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// The 1-based line in the merged file where the lines kept from memory start.
+    pub line: usize,
+    /// The lines as they were read.
+    pub base: Vec<String>,
+    /// The lines in memory, these are the ones kept in the merged file.
+    pub ours: Vec<String>,
+    /// The lines on disk.
+    pub theirs: Vec<String>,
+}
+
+/// The outcome of a three-way merge.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// The conflicts of the merge, in the order they appear in the file.
+    pub conflicts: Vec<Conflict>,
+}
+
+impl MergeReport {
+    /// Returns whether the merge had no conflicts.
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Merges the changes made in `ours` and in `theirs` since `base`, line by line.
+/// Returns the merged text along with the conflicts. See the module documentation for how
+/// conflicts are resolved.
+///
+/// Every line of a todo.txt file stands on its own, so changes to neighbouring lines don't
+/// conflict, only changes to the same lines or insertions at the same place do.
+pub fn merge(base: &str, ours: &str, theirs: &str) -> (String, MergeReport) {
+    let base = base.lines().collect::<Vec<_>>();
+    let sides = [
+        ours.lines().collect::<Vec<_>>(),
+        theirs.lines().collect::<Vec<_>>(),
+    ];
+
+    let mut hunks = sides
+        .iter()
+        .enumerate()
+        .flat_map(|(side, lines)| hunks(side, &matching(&base, lines), base.len(), lines.len()))
+        .collect::<Vec<_>>();
+    hunks.sort_by_key(|h| (h.base.start, h.base.end, h.side));
+
+    let mut merged: Vec<&str> = Vec::new();
+    let mut report = MergeReport::default();
+    let mut pos = 0;
+    let mut rest = hunks.as_slice();
+    while let Some(first) = rest.first() {
+        // the hunks that touch the same lines of the base as the first one
+        let (start, mut end) = (first.base.start, first.base.end);
+        let mut n = 1;
+        while let Some(h) = rest.get(n) {
+            let same_insert = start == end && h.base.start == start && h.base.is_empty();
+            if h.base.start >= end && !same_insert {
+                break;
+            }
+            end = end.max(h.base.end);
+            n += 1;
+        }
+        let (group, next) = rest.split_at(n);
+        rest = next;
+
+        merged.extend(&base[pos..start]);
+        let apply = |side: usize| {
+            let mut lines = Vec::new();
+            let mut at = start;
+            for h in group.iter().filter(|h| h.side == side) {
+                lines.extend(&base[at..h.base.start]);
+                lines.extend(&sides[side][h.lines.clone()]);
+                at = h.base.end;
+            }
+            lines.extend(&base[at..end]);
+            lines
+        };
+        let ours = apply(0);
+        if group.iter().all(|h| h.side == 0) {
+            merged.extend(ours);
+        } else if group.iter().all(|h| h.side == 1) {
+            merged.extend(apply(1));
+        } else {
+            let theirs = apply(1);
+            if ours != theirs {
+                report.conflicts.push(Conflict {
+                    line: merged.len() + 1,
+                    base: to_strings(&base[start..end]),
+                    ours: to_strings(&ours),
+                    theirs: to_strings(&theirs),
+                });
+            }
+            merged.extend(ours);
+        }
+        pos = end;
+    }
+    merged.extend(&base[pos..]);
+
+    let mut text = merged.join("\n");
+    if !merged.is_empty() {
+        text.push('\n');
+    }
+
+    (text, report)
+}
+
+/// A change one side made to the base: the lines of the base replaced with lines of the side.
+struct Hunk {
+    /// 0 for ours, 1 for theirs.
+    side: usize,
+    base: Range<usize>,
+    lines: Range<usize>,
+}
+
+/// Returns the changes between the matched lines of the base and a side.
+fn hunks(side: usize, pairs: &[(usize, usize)], base_len: usize, len: usize) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let (mut b, mut s) = (0, 0);
+    for &(pb, ps) in pairs.iter().chain([(base_len, len)].iter()) {
+        if pb > b || ps > s {
+            hunks.push(Hunk {
+                side,
+                base: b..pb,
+                lines: s..ps,
+            });
+        }
+        (b, s) = (pb + 1, ps + 1);
+    }
+
+    hunks
+}
+
+fn to_strings(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|l| l.to_string()).collect()
+}
+
+/// Returns the pairs of lines of `a` and `b` that stay the same between them, in order.
+/// Uses the linear space variant of Myers' O(ND) diff, so memory stays proportional to the
+/// number of lines. Parts of the files that differ by more than `MAX_COST` edits aren't
+/// searched for common lines, they come out as a single change instead.
+pub(crate) fn matching(a: &[&str], b: &[&str]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    diff(a, b, 0, 0, &mut pairs);
+
+    pairs
+}
+
+/// The number of edits the search for the middle of a change gives up after, which bounds
+/// the time a diff of two very different files takes.
+const MAX_COST: usize = 1024;
+
+/// Adds the matching lines of `a` and `b` to the pairs, `x` and `y` are the offsets of `a` and
+/// `b` in the whole files.
+fn diff(a: &[&str], b: &[&str], x: usize, y: usize, pairs: &mut Vec<(usize, usize)>) {
+    let start = a.iter().zip(b).take_while(|(p, q)| p == q).count();
+    let end = a[start..]
+        .iter()
+        .rev()
+        .zip(b[start..].iter().rev())
+        .take_while(|(p, q)| p == q)
+        .count();
+
+    pairs.extend((0..start).map(|i| (x + i, y + i)));
+    let (inner_a, inner_b) = (&a[start..a.len() - end], &b[start..b.len() - end]);
+    if !inner_a.is_empty() && !inner_b.is_empty() {
+        // the common start and end are gone, so the snake splits the change in two smaller ones
+        if let Some((u, v, len)) = middle_snake(inner_a, inner_b) {
+            let (x, y) = (x + start, y + start);
+            diff(&inner_a[..u], &inner_b[..v], x, y, pairs);
+            pairs.extend((0..len).map(|i| (x + u + i, y + v + i)));
+            diff(
+                &inner_a[u + len..],
+                &inner_b[v + len..],
+                x + u + len,
+                y + v + len,
+                pairs,
+            );
+        }
+    }
+    pairs.extend((0..end).map(|i| (x + a.len() - end + i, y + b.len() - end + i)));
+}
+
+/// Finds the middle snake of Myers' diff: the run of matching lines the middle of a shortest
+/// edit script goes through, found by searching from both ends at once. Returns where the run
+/// starts in `a` and `b` along with its length, or `None` if the search takes more than
+/// `MAX_COST` steps.
+fn middle_snake(a: &[&str], b: &[&str]) -> Option<(usize, usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = ((n + m + 1) / 2).min(MAX_COST as isize);
+    let offset = max + 1;
+    let mut forward = vec![0isize; 2 * offset as usize + 1];
+    let mut backward = vec![0isize; 2 * offset as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+
+    for d in 0..=max {
+        // the furthest reaching path from the start on every diagonal k = x - y
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let (sx, sy) = (x, x - k);
+            while x < n && x - k < m && a[x as usize] == b[(x - k) as usize] {
+                x += 1;
+            }
+            forward[at(k)] = x;
+
+            // the backward paths of the same diagonal have taken d - 1 steps
+            let c = delta - k;
+            if odd && c.abs() < d && x + backward[at(c)] >= n {
+                return Some((sx as usize, sy as usize, (x - sx) as usize));
+            }
+        }
+
+        // the furthest reaching path from the end, counted from the end, on every diagonal
+        for c in (-d..=d).step_by(2) {
+            let mut x = if c == -d || (c != d && backward[at(c - 1)] < backward[at(c + 1)]) {
+                backward[at(c + 1)]
+            } else {
+                backward[at(c - 1)] + 1
+            };
+            let sx = x;
+            while x < n && x - c < m && a[(n - 1 - x) as usize] == b[(m - 1 - (x - c)) as usize] {
+                x += 1;
+            }
+            backward[at(c)] = x;
+
+            let k = delta - c;
+            if !odd && k.abs() <= d && x + forward[at(k)] >= n {
+                let (u, v) = (n - x, m - (x - c));
+                return Some((u as usize, v as usize, (x - sx) as usize));
+            }
+        }
+    }
+
+    None
+}
+
+/// Hashes the content of a file, to notice when it changes.
+/// This is the 64-bit FNV-1a hash, which unlike the hashers of the standard library gives the
+/// same value on every version of Rust, so it can be written to disk like the history does.
+pub(crate) fn hash(content: &str) -> u64 {
+    content.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+impl TodoFile {
+    /// Reads the file on disk, a file that doesn't exist is read as empty.
+    pub(crate) fn read_disk(&self) -> io::Result<String> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns whether another program changed the file on disk since it was read or saved.
+    /// Always returns `false` for a `TodoFile` that wasn't read from disk.
+    pub fn is_modified_on_disk(&self) -> io::Result<bool> {
+        match self.loaded_hash {
+            Some(loaded) => Ok(hash(&self.read_disk()?) != loaded),
+            None => Ok(false),
+        }
+    }
+
+    /// Merges the changes made to the file on disk since it was read into the todo items in
+    /// memory, see the module documentation. The todo items and raw lines are replaced with the
+    /// merged file and the file on disk becomes the new base, so `save` succeeds afterwards.
+    pub fn merge_disk(&mut self) -> io::Result<MergeReport> {
+        let theirs = self.read_disk()?;
+        let (merged, report) = merge(&self.content, &self.render(), &theirs);
+
+        self.content = merged;
+        self.load();
+        self.content = theirs;
+        self.loaded_hash = Some(hash(&self.content));
+
+        Ok(report)
+    }
+
+    /// Merges the changes on disk with `merge_disk` if there are any and saves the file.
+    /// The conflicts are reported, the file is saved with the version in memory of them.
+    pub fn save_merged(&mut self) -> io::Result<MergeReport> {
+        let report = if self.is_modified_on_disk()? {
+            self.merge_disk()?
+        } else {
+            MergeReport::default()
+        };
+        self.save()?;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_merge() {
+    let base = "A\nB\nC\nD\n";
+    let (merged, report) = merge(base, "A\nB2\nC\nD\n", "A\nB\nC\nD2\nE\n");
+    assert_eq!(merged, "A\nB2\nC\nD2\nE\n");
+    assert!(report.is_clean());
+
+    // both sides removed the same line
+    let (merged, report) = merge(base, "A\nC\nD\n", "A\nC\nD\n");
+    assert_eq!(merged, "A\nC\nD\n");
+    assert!(report.is_clean());
+
+    // neighbouring lines changed on each side
+    let (merged, report) = merge(base, "A2\nB\nC\nD\n", "A\nB2\nC\nD\n");
+    assert_eq!(merged, "A2\nB2\nC\nD\n");
+    assert!(report.is_clean());
+
+    let (merged, report) = merge(base, "A\nB mine\nC\nD\n", "A\nB theirs\nC\n");
+    assert_eq!(merged, "A\nB mine\nC\n");
+    assert_eq!(
+        report.conflicts,
+        vec![Conflict {
+            line: 2,
+            base: vec!["B".to_string()],
+            ours: vec!["B mine".to_string()],
+            theirs: vec!["B theirs".to_string()],
+        }]
+    );
+
+    assert_eq!(merge("", "", "").0, "");
+    assert_eq!(merge("", "A\n", "B\n").1.conflicts.len(), 1);
+}
+
+#[test]
+fn test_matching() {
+    let a = ["a", "b", "c", "a", "b", "b", "a"];
+    let b = ["c", "b", "a", "b", "a", "c"];
+    let pairs = matching(&a, &b);
+    assert_eq!(pairs.len(), 4);
+    assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+    assert!(pairs.iter().all(|&(x, y)| a[x] == b[y]));
+}
+
+#[test]
+fn test_matching_big_change() {
+    let a = (0..4000).map(|i| i.to_string()).collect::<Vec<_>>();
+    let a = a.iter().map(String::as_str).collect::<Vec<_>>();
+    let b = a.iter().rev().copied().collect::<Vec<_>>();
+    let pairs = matching(&a, &b);
+    assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+    assert!(pairs.iter().all(|&(x, y)| a[x] == b[y]));
+
+    // small changes to a big file are still found exactly
+    let mut c = a.clone();
+    c.remove(10);
+    c.insert(3000, "new");
+    c[2000] = "changed";
+    let pairs = matching(&a, &c);
+    assert_eq!(pairs.len(), a.len() - 2);
+    assert!(pairs.iter().all(|&(x, y)| a[x] == c[y]));
+}
+
+#[test]
+fn test_hash() {
+    assert_eq!(hash(""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(hash("foobar"), 0x8594_4171_f739_67e8);
+}
@@ -0,0 +1,146 @@
+//! # Parallel
+//!
+//! Spreading the work over the lines or todo items of a big file across threads.
+//!
+//! With the `parallel` feature, the items are split into one contiguous chunk per available
+//! thread and the chunks are processed on scoped threads. The results are put back together in
+//! the order of the chunks, so they are the same as the results of processing the items one
+//! after the other, which is what happens without the feature or for small files.
+
+/// The number of items below which the work stays on the calling thread, where starting threads
+/// costs more than it saves.
+#[cfg(feature = "parallel")]
+const MIN_LEN: usize = 1024;
+
+/// Runs `f` over contiguous chunks of the items and returns the results in order.
+/// `f` gets the index of the first item of its chunk along with the chunk.
+#[cfg(feature = "parallel")]
+pub(crate) fn chunks<'a, T: Sync, U: Send>(
+    items: &'a [T],
+    f: impl Fn(usize, &'a [T]) -> U + Sync,
+) -> Vec<U> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if items.len() < MIN_LEN || threads < 2 {
+        return vec![f(0, items)];
+    }
+
+    let size = items.len().div_ceil(threads);
+    let f = &f;
+    std::thread::scope(|s| {
+        let handles = items
+            .chunks(size)
+            .enumerate()
+            .map(|(i, chunk)| s.spawn(move || f(i * size, chunk)))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    })
+}
+
+/// Runs `f` over the items and returns the results in order.
+/// `f` gets the index of the first item of its chunk along with the chunk.
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn chunks<'a, T: Sync, U: Send>(
+    items: &'a [T],
+    f: impl Fn(usize, &'a [T]) -> U + Sync,
+) -> Vec<U> {
+    vec![f(0, items)]
+}
+
+/// Maps every item along with its index and returns the results in order.
+pub(crate) fn map<T: Sync, U: Send>(items: &[T], f: impl Fn(usize, &T) -> U + Sync) -> Vec<U> {
+    chunks(items, |start, chunk| {
+        chunk
+            .iter()
+            .enumerate()
+            .map(|(i, item)| f(start + i, item))
+            .collect::<Vec<_>>()
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Returns clones of the items the predicate holds for, in order.
+pub(crate) fn filter<T: Sync + Clone + Send>(items: &[T], f: impl Fn(&T) -> bool + Sync) -> Vec<T> {
+    chunks(items, |_, chunk| {
+        chunk
+            .iter()
+            .filter(|item| f(item))
+            .cloned()
+            .collect::<Vec<_>>()
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Collects the names `f` returns for the items, sorted and deduplicated.
+pub(crate) fn names<'a, T: Sync, I>(items: &'a [T], f: impl Fn(&'a T) -> I + Sync) -> Vec<String>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut names = chunks(items, |_, chunk| {
+        let mut names = chunk.iter().flat_map(&f).collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    })
+    .concat();
+    names.sort_unstable();
+    names.dedup();
+
+    names.into_iter().map(str::to_string).collect()
+}
+
+#[cfg(test)]
+#[test]
+fn test_order() {
+    let items = (0..5000).collect::<Vec<usize>>();
+    assert_eq!(
+        map(&items, |i, n| i * n),
+        (0..5000).map(|n| n * n).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        filter(&items, |n| n % 7 == 0),
+        (0..5000).filter(|n| n % 7 == 0).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_big_file() {
+    let content = (0..3000)
+        .map(|i| match i % 4 {
+            0 => format!("(B) Task {} +p{}", i, i % 13),
+            1 => "# comment".to_string(),
+            2 => format!("x Task {} @c{} due:2024-13-{}", i, i % 5, i),
+            _ => format!("Task {} id:{}", i, 100_000 + i),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let t = crate::file::TodoFile::from_string(&content);
+
+    let serial = content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, l)| crate::file::parse_line(i, l, Default::default()).ok())
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        t.todos.iter().map(|t| t.to_string()).collect::<Vec<_>>(),
+        serial
+    );
+    let ids = t
+        .todos
+        .iter()
+        .filter(|t| !t.has_id_tag())
+        .map(|t| t.id())
+        .collect::<Vec<_>>();
+    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(t.render(), format!("{}\n", content));
+    assert_eq!(t.list_projects().len(), 13);
+    assert_eq!(t.search("Task 2999").len(), 1);
+    assert_eq!(t.regex(r"^\(B\)").unwrap().len(), 750);
+}
@@ -10,12 +10,15 @@
 //! So if you only need the project of a todo item, you can simply call the `parse_project`
 //! function and it will return the project of the todo item without even knowing the other fields.
 //!
-//! The struct parses using `fancy_regex` which is a regex library that is highly optimized for
-//! speed and performance. All of the parsing is done using regex and hence is very fast and also
-//! intuitively easy to understand.
+//! The struct parses using the single pass `token::Lexer`, which walks the line once and
+//! doesn't allocate. The `parse_*` functions run the lexer through a borrowed
+//! `view::TodoView`, so no regex has to be compiled for each call.
 //!
 //! The struct also implements the `Display` trait which ensures that the struct can be printed
 //! as found in the todo.txt file with all of the changes that have been made to the todo item.
+//! The line is kept as a list of tokens (see the `token` module), so a todo item that was never
+//! edited is printed exactly as it was read, and an edited one only changes the tokens of the
+//! fields that were touched.
 //!
 //! ```rust
 //! use libdonow::parser::Todo;
@@ -26,10 +29,18 @@
 //! println!("{}", t.parse_context().unwrap().unwrap());
 //! ```
 
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, ops::Range, str::FromStr};
 
-use fancy_regex::Regex;
-use hashbrown::HashMap;
+use crate::{
+    builder::TodoBuilder,
+    dates,
+    id::{self, TodoId, ID_TAG},
+    priority::Priority,
+    recur::Recurrence,
+    tags::Tags,
+    token::{self, Lexer, Token, TokenKind},
+    view::TodoView,
+};
 
 /// A struct representing a single todo item.
 /// A new todo item can be created using the `new` function which takes a string slice as an
@@ -55,19 +66,52 @@ pub struct Todo {
     /// The status of the todo item.
     pub completed: bool,
     /// The priority of the todo item.
-    pub priority: Option<String>,
+    pub priority: Option<Priority>,
     /// The completion date of the todo item.
     pub completion: Option<chrono::NaiveDate>,
     /// The creation date of the todo item.
     pub creation: Option<chrono::NaiveDate>,
-    /// The project of the todo item.
-    pub project: Option<String>,
-    /// The context of the todo item.
-    pub context: Option<String>,
+    /// The projects of the todo item in the order they appear in the line.
+    pub projects: Vec<String>,
+    /// The contexts of the todo item in the order they appear in the line.
+    pub contexts: Vec<String>,
     /// The tags of the todo item.
-    pub others: HashMap<String, String>,
+    pub others: Tags,
     /// The content of the todo item.
+    /// Kept in sync with the fields by the setters, call `sync` after changing a field directly.
     pub content: String,
+    /// The tokens of the line the todo item was parsed from.
+    #[serde(skip)]
+    tokens: Vec<Token>,
+    /// The mode the todo item is parsed with.
+    #[serde(skip)]
+    mode: ParseMode,
+    /// The id of the todo item when it has no `id:` tag.
+    #[serde(skip, default = "id::next")]
+    id: TodoId,
+}
+
+/// The rules used to find the header of a todo item.
+///
+/// The strict mode follows the todo.txt format: only `x ` marks a todo item as completed, the
+/// priority is `(A)` to `(Z)` at the start of the line (or right after the completion marker)
+/// and the dates follow it, with the completion date first on completed todo items.
+///
+/// The lenient mode reads the header anywhere in the line: any line starting with `x` is
+/// completed, the first `(A)` to `(Z)` anywhere is the priority and the first two dates
+/// anywhere are the creation and completion dates. Older versions of the library read every
+/// line like this, but they also took any `(word)` as the priority, which neither mode does.
+///
+/// The strict mode is the default since it was added, which is a breaking change: a line like
+/// `xylophone practice` isn't completed anymore and the first date of a completed todo item
+/// is now its completion date. Pass `ParseMode::Lenient` to keep reading the header anywhere.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Follow the todo.txt format.
+    #[default]
+    Strict,
+    /// Accept the header anywhere in the line.
+    Lenient,
 }
 
 impl Todo {
@@ -82,29 +126,62 @@ impl Todo {
     /// This is not needed if you only need a part of the todo item. In that case, you can use
     /// the other parsing functions.
     pub fn parse(s: &str) -> Result<Self, TodoErr> {
+        Todo::parse_with(s, ParseMode::default())
+    }
+
+    /// Parses a todo item from a string slice using the given `ParseMode`.
+    /// `Todo::parse` uses the strict mode, pass `ParseMode::Lenient` to read the header
+    /// anywhere in the line, see `ParseMode`.
+    pub fn parse_with(s: &str, mode: ParseMode) -> Result<Self, TodoErr> {
         let mut t = Todo::new(s);
-        t.completed = t.content.starts_with('x');
-        if t.completed {
-            t.content = t.content[1..].trim().to_string();
+        t.mode = mode;
+
+        let mut words = Vec::new();
+        for tok in Lexer::new(s, mode) {
+            let text = tok.text;
+            t.tokens.push(Token::new(tok.kind, text));
+            match tok.kind {
+                TokenKind::Completed => t.completed = true,
+                TokenKind::Priority => t.priority = Some(parse_priority_at(text, tok.span)?),
+                TokenKind::CompletionDate => t.completion = Some(parse_date_at(text, tok.span)?),
+                TokenKind::CreationDate => t.creation = Some(parse_date_at(text, tok.span)?),
+                TokenKind::Project => t.projects.push(text[1..].to_string()),
+                TokenKind::Context => t.contexts.push(text[1..].to_string()),
+                TokenKind::Tag => {
+                    if let Some((k, v)) = text.split_once(':') {
+                        t.others.append(k, v);
+                    }
+                }
+                TokenKind::Word => words.push(text),
+                _ => {}
+            }
         }
-        t.project = t.parse_project()?;
-        t.context = t.parse_context()?;
-        t.others = t.parse_tags()?;
-        t.priority = t.parse_priority()?;
-        t.title = t.parse_title()?;
 
-        let dates = t.parse_dates()?;
-        t.creation = dates.0;
-        t.completion = dates.1;
+        if words.is_empty() {
+            return Err(TodoErr::NoTitle {
+                at: Location::new(0..s.len()),
+            });
+        }
+        t.title = words.join(" ");
+        if let Some(tag) = t.tag_id() {
+            id::seen(tag);
+            t.id = tag;
+        }
 
         Ok(t)
     }
 
+    /// Starts building a todo item with the given title.
+    /// See `builder::TodoBuilder` for the parts that can be set.
+    pub fn builder(title: &str) -> TodoBuilder {
+        TodoBuilder::new(title)
+    }
+
     /// Fills in the missing fields of the todo item.
     /// It uses the `parse` function to parse the todo item and then replaces the current todo
     /// item with the parsed todo item.
     pub fn fill(&mut self) -> Result<(), TodoErr> {
-        let t = Todo::parse(&self.content)?;
+        let t = Todo::parse_with(&self.content, self.mode)?;
         *self = t;
 
         Ok(())
@@ -115,15 +192,24 @@ impl Todo {
     /// correct format.
     /// It is still expermental and may not work as expected.
     pub fn smart_parse(s: &str) -> Result<Self, TodoErr> {
+        Todo::smart_parse_on(s, chrono::Local::now().naive_local().date())
+    }
+
+    /// Works the same as `smart_parse`, but uses the given date as today.
+    /// The date is used as the missing creation date and relative dates like `due:tomorrow`
+    /// are resolved against it.
+    pub fn smart_parse_on(s: &str, today: chrono::NaiveDate) -> Result<Self, TodoErr> {
         let mut t = Todo::parse(s)?;
 
         if t.creation.is_none() {
-            t.creation = Some(chrono::Local::now().naive_local().date());
+            t.creation = Some(today);
         }
+        t.resolve_dates(today);
 
         if t.priority.is_none() {
-            t.priority = Some("D".to_string());
+            t.priority = Priority::new('D');
         }
+        t.sync();
 
         Ok(t)
     }
@@ -139,84 +225,266 @@ impl Todo {
             priority: None,
             completion: None,
             creation: None,
-            project: None,
-            context: None,
-            others: HashMap::new(),
+            projects: Vec::new(),
+            contexts: Vec::new(),
+            others: Tags::new(),
             content: s.to_string(),
+            tokens: Vec::new(),
+            mode: ParseMode::default(),
+            id: id::next(),
         }
     }
 
+    /// Returns the `ParseMode` the todo item is parsed with.
+    pub fn mode(&self) -> ParseMode {
+        self.mode
+    }
+
+    /// Returns the id of the todo item, see the `id` module.
+    /// This is the value of its `id:` tag if it is a number up to `id::MAX_ID`, or the id it
+    /// got when it was created otherwise.
+    pub fn id(&self) -> TodoId {
+        self.tag_id().unwrap_or(self.id)
+    }
+
+    /// Returns whether the id of the todo item comes from an `id:` tag.
+    pub fn has_id_tag(&self) -> bool {
+        self.tag_id().is_some()
+    }
+
+    /// Writes the id of the todo item into an `id:` tag, unless it has one already.
+    pub fn persist_id(&mut self) {
+        if !self.has_id_tag() {
+            self.set_id_tag();
+        }
+    }
+
+    fn tag_id(&self) -> Option<TodoId> {
+        let id: TodoId = self.others.get(ID_TAG)?.parse().ok()?;
+        (id.0 <= id::MAX_ID).then_some(id)
+    }
+
+    /// Gives the todo item a new id, rewriting its `id:` tag if it has one.
+    pub(crate) fn renumber(&mut self) {
+        self.id = id::next();
+        if self.has_id_tag() {
+            self.set_id_tag();
+        }
+    }
+
+    fn set_id_tag(&mut self) {
+        self.others.set(ID_TAG, &self.id.to_string());
+        self.sync();
+    }
+
+    /// Takes over the id of the todo item this one replaces, unless it has an `id:` tag.
+    pub(crate) fn keep_id(&mut self, old: &Todo) {
+        if !self.has_id_tag() {
+            self.id = old.id();
+        }
+    }
+
+    /// Returns a borrowed `TodoView` over the content of the todo item.
+    /// All of the `parse_*` functions below are built on top of it.
+    pub fn view(&self) -> TodoView<'_> {
+        TodoView::with_mode(&self.content, self.mode)
+    }
+
     /// Parses the due date of the todo item.
     /// This function returns an `Option` with the `NaiveDate` of the due date.
+    /// A `due:` tag that isn't a valid date returns an `InvalidDate` error.
     pub fn parse_due(&self) -> Result<Option<chrono::NaiveDate>, TodoErr> {
-        let date_re =
-            Regex::new("due:(\\d{4}-\\d{2}-\\d{2})").map_err(|_| TodoErr::RegexParseErr)?;
-        match date_re.find(&self.content) {
-            Ok(s) => Ok(s.map(|p| {
-                chrono::NaiveDate::parse_from_str(&p.as_str()[4..], "%Y-%m-%d")
-                    .expect("Failed to parse date")
-            })),
-            Err(_) => Err(TodoErr::RegexParseErr),
+        self.parse_date_tag("due")
+    }
+
+    /// Parses the due date of the todo item, resolving a relative date like `due:tomorrow`
+    /// against the given date. See the `dates` module for the supported forms.
+    /// A `due:` tag that isn't a date in any of the forms returns an `InvalidDate` error.
+    pub fn parse_due_on(
+        &self,
+        today: chrono::NaiveDate,
+    ) -> Result<Option<chrono::NaiveDate>, TodoErr> {
+        self.find_tag("due")
+            .map(|(v, span)| {
+                dates::resolve(v, today).ok_or_else(|| TodoErr::InvalidDate {
+                    value: v.to_string(),
+                    at: Location::new(span),
+                })
+            })
+            .transpose()
+    }
+
+    /// Rewrites the relative dates in the `due:` and `t:` tags into `YYYY-MM-DD` dates,
+    /// resolving them against the given date. See the `dates` module for the supported forms.
+    /// Values that aren't dates are left alone.
+    /// Returns whether any of the tags changed.
+    pub fn resolve_dates(&mut self, today: chrono::NaiveDate) -> bool {
+        let resolved = self
+            .others
+            .iter()
+            .filter(|(k, _)| dates::DATE_TAGS.contains(k))
+            .filter_map(|(k, v)| {
+                dates::resolve_relative(v, today)
+                    .map(|d| (k.to_string(), v.to_string(), d.to_string()))
+            })
+            .collect::<Vec<_>>();
+
+        for (key, value, date) in &resolved {
+            self.others.replace_value(key, value, date);
+        }
+        if !resolved.is_empty() {
+            self.sync();
+        }
+
+        !resolved.is_empty()
+    }
+
+    /// Parses the threshold date of the todo item from the `t:` tag.
+    /// The threshold is the date before which the todo item shouldn't be shown.
+    /// A `t:` tag that isn't a valid date returns an `InvalidDate` error.
+    pub fn parse_threshold(&self) -> Result<Option<chrono::NaiveDate>, TodoErr> {
+        self.parse_date_tag("t")
+    }
+
+    /// Returns whether the todo item should be shown on the given date.
+    /// A todo item is hidden until its threshold date, one without a valid threshold is always
+    /// shown.
+    pub fn is_visible_on(&self, date: chrono::NaiveDate) -> bool {
+        match self.parse_threshold() {
+            Ok(Some(t)) => t <= date,
+            _ => true,
+        }
+    }
+
+    /// Parses the first tag with the given key as a date.
+    fn parse_date_tag(&self, key: &str) -> Result<Option<chrono::NaiveDate>, TodoErr> {
+        self.find_tag(key)
+            .map(|(v, span)| parse_date_at(v, span))
+            .transpose()
+    }
+
+    /// Finds the first tag with the given key in the content.
+    /// Returns the value along with its byte span in the line.
+    fn find_tag(&self, key: &str) -> Option<(&str, Range<usize>)> {
+        self.view()
+            .lexemes()
+            .filter(|l| l.kind == TokenKind::Tag)
+            .find(|l| l.text.split_once(':').is_some_and(|(k, _)| k == key))
+            .map(|l| {
+                let start = l.span.start + key.len() + 1;
+                (&l.text[key.len() + 1..], start..l.span.end)
+            })
+    }
+
+    /// Parses the recurrence of the todo item from the `rec:` tag.
+    /// A `rec:` tag that isn't in the format `1w` or `+1w` returns an `InvalidRecurrence`
+    /// error. See the `recur` module for the supported formats.
+    pub fn parse_recurrence(&self) -> Result<Option<Recurrence>, TodoErr> {
+        self.find_tag("rec")
+            .map(|(v, span)| {
+                v.parse().map_err(|_| TodoErr::InvalidRecurrence {
+                    value: v.to_string(),
+                    at: Location::new(span),
+                })
+            })
+            .transpose()
+    }
+
+    /// Builds the next occurrence of a recurring todo item that is completed on the given date.
+    /// Returns `None` if the todo item has no `rec:` tag.
+    ///
+    /// The copy is open, has its priority restored and is created on the completion date if
+    /// the todo item had a creation date. Its `due:` date is moved forward from the completion
+    /// date, or from the old due date for a strict recurrence. Its `t:` date keeps the same
+    /// distance to the due date, or is moved forward the same way when there is no due date.
+    /// A todo item with neither date gets a due date. The copy gets a new id, see the `id`
+    /// module.
+    pub fn next_recurrence(&self, on: chrono::NaiveDate) -> Result<Option<Todo>, TodoErr> {
+        let rec = match self.parse_recurrence()? {
+            Some(rec) => rec,
+            None => return Ok(None),
+        };
+        let due = self.parse_due()?;
+        let threshold = self.parse_threshold()?;
+        let advance = |base: chrono::NaiveDate| {
+            let from = if rec.strict { base } else { on };
+            rec.advance(from).ok_or_else(|| TodoErr::InvalidDate {
+                value: from.to_string(),
+                at: Location::new(0..self.content.len()),
+            })
+        };
+
+        let mut next = self.clone();
+        next.reopen();
+        if next.creation.is_some() {
+            next.creation = Some(on);
+        }
+
+        match (due, threshold) {
+            (Some(due), threshold) => {
+                let new_due = advance(due)?;
+                next.others.set("due", &new_due.to_string());
+                if let Some(t) = threshold {
+                    let new_t = if rec.strict {
+                        advance(t)?
+                    } else {
+                        new_due - (due - t)
+                    };
+                    next.others.set("t", &new_t.to_string());
+                }
+            }
+            (None, Some(t)) => next.others.set("t", &advance(t)?.to_string()),
+            (None, None) => next.others.set("due", &advance(on)?.to_string()),
         }
+
+        next.sync();
+        next.renumber();
+
+        Ok(Some(next))
     }
 
     /// Parses the project of the todo item.
-    /// If there are two projects in the todo item, it returns the first one
+    /// If there are two projects in the todo item, it returns the first one.
+    /// Use `parse_projects` to get all of them.
     /// A project is in the format `+project`
     pub fn parse_project(&self) -> Result<Option<String>, TodoErr> {
-        let project_re = Regex::new("\\+(\\w+)").map_err(|_| TodoErr::RegexParseErr)?;
-        match project_re.find(&self.content) {
-            Ok(s) => Ok(s.map(|p| p.as_str()[1..].to_string())),
-            Err(_) => Err(TodoErr::RegexParseErr),
-        }
+        Ok(self.view().projects().next().map(str::to_string))
+    }
+
+    /// Parses all of the projects of the todo item in the order they appear.
+    pub fn parse_projects(&self) -> Result<Vec<String>, TodoErr> {
+        Ok(self.view().projects().map(str::to_string).collect())
     }
 
     /// Parses the context of the todo item.
-    /// If there are two contexts in the todo item, it returns the first one
+    /// If there are two contexts in the todo item, it returns the first one.
+    /// Use `parse_contexts` to get all of them.
     /// A context is in the format `@context`
     pub fn parse_context(&self) -> Result<Option<String>, TodoErr> {
-        let context_re = Regex::new("\\@(\\w+)").map_err(|_| TodoErr::RegexParseErr)?;
-        match context_re.find(&self.content) {
-            Ok(s) => Ok(s.map(|p| p.as_str()[1..].to_string())),
-            Err(_) => Err(TodoErr::RegexParseErr),
-        }
+        Ok(self.view().contexts().next().map(str::to_string))
+    }
+
+    /// Parses all of the contexts of the todo item in the order they appear.
+    pub fn parse_contexts(&self) -> Result<Vec<String>, TodoErr> {
+        Ok(self.view().contexts().map(str::to_string).collect())
     }
 
     /// Parses the tags of the todo item.
     /// Tags are in the format `key:value` and are separated by a space.
-    pub fn parse_tags(&self) -> Result<HashMap<String, String>, TodoErr> {
-        let tags_re = Regex::new("(\\w+):(\\S+)").map_err(|_| TodoErr::RegexParseErr)?;
-        let mut map = HashMap::new();
-
-        let iter = tags_re.find_iter(&self.content);
-        iter.for_each(|e| {
-            if let Ok(e) = e {
-                let split = e.as_str().to_string();
-                if split.split_once(':').is_some() {
-                    let (k, v) = split.split_once(':').unwrap();
-                    map.insert(k.to_string(), v.to_string());
-                }
-            }
-        });
-
-        Ok(map)
+    /// Duplicate keys are kept in the order they appear.
+    pub fn parse_tags(&self) -> Result<Tags, TodoErr> {
+        Ok(self.view().tags().collect())
     }
 
     /// Parses the priority of the todo item.
     /// A priority is in the format `(A)` and is at the start of the todo item.
-    pub fn parse_priority(&self) -> Result<Option<String>, TodoErr> {
-        let p_re = Regex::new("\\((\\w+)\\)").map_err(|_| TodoErr::RegexParseErr)?;
-        match p_re.find(&self.content) {
-            Ok(s) => Ok(s.map(|p| {
-                p.as_str()
-                    .strip_prefix('(')
-                    .unwrap()
-                    .strip_suffix(')')
-                    .unwrap()
-                    .to_string()
-            })),
-            Err(_) => Err(TodoErr::RegexParseErr),
-        }
+    /// Where the priority is looked for depends on the `ParseMode` of the todo item.
+    pub fn parse_priority(&self) -> Result<Option<Priority>, TodoErr> {
+        self.view()
+            .lexemes()
+            .find(|l| l.kind == TokenKind::Priority)
+            .map(|l| parse_priority_at(l.text, l.span))
+            .transpose()
     }
 
     /// Experimental: Parses the hashtags of the todo item.
@@ -224,73 +492,283 @@ impl Todo {
     ///
     /// These are not supported by the todo.txt format and are an experimental feature.
     pub fn parse_hashtags(&self) -> Result<Vec<String>, TodoErr> {
-        let tags_re = Regex::new("#(\\w+)").map_err(|_| TodoErr::RegexParseErr)?;
-        let mut tags = Vec::new();
-
-        let iter = tags_re.find_iter(&self.content);
-        iter.for_each(|e| {
-            if let Ok(e) = e {
-                tags.push(e.as_str().to_string());
-            }
-        });
-
-        Ok(tags)
+        Ok(self.view().hashtags().map(str::to_string).collect())
     }
 
     /// Parses the title of the todo item.
     /// It is guaranteed that the title will be returned and if there is no title, it will return
     /// an error.
     pub fn parse_title(&self) -> Result<String, TodoErr> {
-        let mut content = self.content.clone();
-        if content.starts_with('x') {
-            content = content[1..].trim().to_string();
-        }
-
-        // remove anything that starts with a +, @ or () or some:word or a date with - or :
-        let re =
-            Regex::new("(\\+(\\w+)|\\@(\\w+)|\\((\\w+)\\)|\\w+:(\\S+)|(?<!:)\\d{4}-\\d{2}-\\d{2})")
-                .map_err(|_| TodoErr::RegexParseErr)?;
-        let title = re.replace_all(&content, "");
+        let title = self.view().title_words().collect::<Vec<_>>().join(" ");
 
-        if title.trim().is_empty() {
-            Err(TodoErr::NoTitle)
+        if title.is_empty() {
+            Err(TodoErr::NoTitle {
+                at: Location::new(0..self.content.len()),
+            })
         } else {
-            Ok(title.trim().to_string())
+            Ok(title)
         }
     }
 
     /// Parses the dates of the todo item.
     /// The function returns a tuple with the creation date and the completion date.
+    /// Where the dates are looked for depends on the `ParseMode` of the todo item.
     pub fn parse_dates(
         &self,
     ) -> Result<(Option<chrono::NaiveDate>, Option<chrono::NaiveDate>), TodoErr> {
-        let date_re =
-            Regex::new("(?<!:)(\\d{4}-\\d{2}-\\d{2})").map_err(|_| TodoErr::RegexParseErr)?;
-        let mut dates = date_re.find_iter(&self.content);
-
         let mut creation = None;
         let mut completion = None;
+        for l in self.view().lexemes() {
+            match l.kind {
+                TokenKind::CreationDate => creation = Some(parse_date_at(l.text, l.span)?),
+                TokenKind::CompletionDate => completion = Some(parse_date_at(l.text, l.span)?),
+                _ => {}
+            }
+        }
+
+        Ok((creation, completion))
+    }
+
+    /// Returns the tokens of the line the todo item was parsed from.
+    /// These reflect changes made through the methods of the todo item, but not changes made
+    /// to the fields directly until `sync` is called.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Brings the tokens of the line up to date with the fields of the todo item.
+    /// Tokens that still match their field are kept untouched, so an unchanged todo item
+    /// renders exactly as it was read. Fields that were added are inserted in the todo.txt
+    /// order and fields that were removed take their surrounding space with them.
+    fn sync_tokens(&self) -> Vec<Token> {
+        let mut tokens = self.tokens.clone();
+
+        let header = header(self.mode);
+        let completed = self.completed.then(|| "x".to_string());
+        sync_header(&mut tokens, header, TokenKind::Completed, completed);
+        let priority = self.priority.as_ref().map(|p| format!("({})", p));
+        sync_header(&mut tokens, header, TokenKind::Priority, priority);
+        let creation = self.creation.map(|d| d.to_string());
+        sync_header(&mut tokens, header, TokenKind::CreationDate, creation);
+        let completion = self.completion.map(|d| d.to_string());
+        sync_header(&mut tokens, header, TokenKind::CompletionDate, completion);
+
+        sync_title(&mut tokens, &self.title);
+
+        sync_list(&mut tokens, TokenKind::Project, '+', &self.projects);
+        sync_list(&mut tokens, TokenKind::Context, '@', &self.contexts);
 
-        if let Some(date) = dates.next() {
-            creation = Some(
-                chrono::NaiveDate::parse_from_str(date.unwrap().as_str(), "%Y-%m-%d")
-                    .expect("Failed to parse date"),
-            );
+        sync_tags(&mut tokens, &self.others);
+
+        tokens
+    }
+
+    /// Brings the `content` and the tokens of the todo item up to date with its fields.
+    /// The setters and the other methods that change the todo item call this on their own,
+    /// it only has to be called after changing the public fields directly. Until then, the
+    /// `parse_*` functions and the queries of `TodoFile` that read the `content` see the old
+    /// line.
+    ///
+    /// A todo item created with `Todo::new` and never parsed has no tokens yet, so its content
+    /// is rebuilt from its fields alone.
+    pub fn sync(&mut self) {
+        self.tokens = self.sync_tokens();
+        self.content = token::render(&self.tokens);
+    }
+
+    /// Sets the title of the todo item. Runs of whitespace in the title become single spaces.
+    ///
+    /// Returns a `TodoErr::NoTitle` error if the title is empty and a `TodoErr::InvalidTodo`
+    /// error if the title would be read back as something else, like the `+mom` of
+    /// `Call +mom` turning into a project. The todo item doesn't change on an error.
+    pub fn set_title(&mut self, title: &str) -> Result<(), TodoErr> {
+        let mut t = self.clone();
+        t.title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        t.sync();
+        if Todo::parse_with(&t.content, t.mode)?.title != t.title {
+            return Err(TodoErr::InvalidTodo(format!(
+                "`{}` isn't read back as the title",
+                t.title
+            )));
         }
 
-        if let Some(date) = dates.next() {
-            completion = Some(
-                chrono::NaiveDate::parse_from_str(date.unwrap().as_str(), "%Y-%m-%d")
-                    .expect("Failed to parse date"),
-            );
+        *self = t;
+        Ok(())
+    }
+
+    /// Sets the priority of the todo item, `None` removes it like `todo.sh depri`.
+    pub fn set_priority(&mut self, priority: Option<Priority>) {
+        self.priority = priority;
+        self.sync();
+    }
+
+    /// Sets whether the todo item is completed.
+    /// A todo item is completed on the current date with `complete` and reopened with
+    /// `reopen`, so the dates and the priority change along with the status.
+    pub fn set_completed(&mut self, completed: bool) {
+        if completed {
+            self.complete(chrono::Local::now().naive_local().date());
+        } else {
+            self.reopen();
         }
+    }
 
-        Ok((creation, completion))
+    /// Sets the creation date of the todo item.
+    pub fn set_creation(&mut self, date: Option<chrono::NaiveDate>) {
+        self.creation = date;
+        self.sync();
+    }
+
+    /// Sets the completion date of the todo item.
+    pub fn set_completion(&mut self, date: Option<chrono::NaiveDate>) {
+        self.completion = date;
+        self.sync();
+    }
+
+    /// Sets the due date of the todo item in the `due:` tag, `None` removes the tag.
+    pub fn set_due(&mut self, date: Option<chrono::NaiveDate>) {
+        self.set_date_tag("due", date);
+    }
+
+    /// Sets the threshold date of the todo item in the `t:` tag, `None` removes the tag.
+    pub fn set_threshold(&mut self, date: Option<chrono::NaiveDate>) {
+        self.set_date_tag("t", date);
+    }
+
+    fn set_date_tag(&mut self, key: &str, date: Option<chrono::NaiveDate>) {
+        match date {
+            Some(d) => self.others.set(key, &d.to_string()),
+            None => {
+                self.others.remove(key);
+            }
+        }
+        self.sync();
+    }
+
+    /// Sets the key to a single value. See `Tags::set`.
+    /// Returns a `TodoErr::InvalidTodo` error and doesn't change the todo item if `key:value`
+    /// wouldn't be read back as a tag, like a value with a space in it.
+    pub fn set_tag(&mut self, key: &str, value: &str) -> Result<(), TodoErr> {
+        if !token::is_tag_of(key, value) {
+            return Err(TodoErr::InvalidTodo(format!(
+                "`{}:{}` isn't a valid tag",
+                key, value
+            )));
+        }
+
+        self.others.set(key, value);
+        self.sync();
+        Ok(())
+    }
+
+    /// Removes all of the tags with the key and returns their values.
+    pub fn remove_tag(&mut self, key: &str) -> Vec<String> {
+        let removed = self.others.remove(key);
+        self.sync();
+        removed
+    }
+
+    /// Adds a project to the todo item if it doesn't have it already.
+    /// The project is given without the leading `+`. Returns a `TodoErr::InvalidTodo` error
+    /// and doesn't change the todo item if it wouldn't be read back as a project, like
+    /// `two words`.
+    pub fn add_project(&mut self, project: &str) -> Result<(), TodoErr> {
+        if !token::is_name(project) {
+            return Err(TodoErr::InvalidTodo(format!(
+                "`+{}` isn't a valid project",
+                project
+            )));
+        }
+
+        if !self.projects.iter().any(|p| p == project) {
+            self.projects.push(project.to_string());
+            self.sync();
+        }
+        Ok(())
+    }
+
+    /// Removes a project from the todo item.
+    /// Returns whether the todo item had the project.
+    pub fn remove_project(&mut self, project: &str) -> bool {
+        let len = self.projects.len();
+        self.projects.retain(|p| p != project);
+        self.sync();
+        len != self.projects.len()
+    }
+
+    /// Adds a context to the todo item if it doesn't have it already.
+    /// The context is given without the leading `@`. Returns a `TodoErr::InvalidTodo` error
+    /// and doesn't change the todo item if it wouldn't be read back as a context.
+    pub fn add_context(&mut self, context: &str) -> Result<(), TodoErr> {
+        if !token::is_name(context) {
+            return Err(TodoErr::InvalidTodo(format!(
+                "`@{}` isn't a valid context",
+                context
+            )));
+        }
+
+        if !self.contexts.iter().any(|c| c == context) {
+            self.contexts.push(context.to_string());
+            self.sync();
+        }
+        Ok(())
     }
 
-    /// Toggles the status of the todo item.
+    /// Removes a context from the todo item.
+    /// Returns whether the todo item had the context.
+    pub fn remove_context(&mut self, context: &str) -> bool {
+        let len = self.contexts.len();
+        self.contexts.retain(|c| c != context);
+        self.sync();
+        len != self.contexts.len()
+    }
+
+    /// Toggles the status of the todo item, see `set_completed`.
     pub fn toggle_status(&mut self) {
-        self.completed = !self.completed;
+        self.set_completed(!self.completed);
+    }
+
+    /// Marks the todo item as completed on the given date, like `todo.sh do`.
+    /// The completion date is set and the priority is moved into a `pri:` tag so that it can
+    /// be restored by `reopen`. The todo.txt format requires a completion date on a completed
+    /// todo item with a creation date, but no creation date is made up for a todo item
+    /// without one.
+    ///
+    /// The lenient mode reads a lone date as the creation date, so there a todo item without
+    /// a creation date is completed without a completion date.
+    ///
+    /// Completing a todo item that is already completed does nothing.
+    pub fn complete(&mut self, on: chrono::NaiveDate) {
+        if self.completed {
+            return;
+        }
+
+        self.completed = true;
+        if self.mode == ParseMode::Strict || self.creation.is_some() {
+            self.completion = Some(on);
+        }
+        if let Some(p) = self.priority.take() {
+            self.others.set("pri", &p.to_string());
+        }
+        self.sync();
+    }
+
+    /// Marks a completed todo item as not completed.
+    /// The completion date is cleared and the priority is restored from the `pri:` tag that
+    /// `complete` left behind.
+    ///
+    /// Reopening a todo item that isn't completed does nothing.
+    pub fn reopen(&mut self) {
+        if !self.completed {
+            return;
+        }
+
+        self.completed = false;
+        self.completion = None;
+        if let Some(p) = self.others.remove("pri").into_iter().next() {
+            self.priority = p.parse().ok();
+        }
+        self.sync();
     }
 
     /// Pretty prints the todo item.
@@ -305,11 +783,11 @@ impl Todo {
         if let Some(p) = &self.priority {
             println!("Priority: {}", p);
         }
-        if let Some(p) = &self.project {
+        for p in &self.projects {
             println!("Project: {}", p);
         }
-        if let Some(p) = &self.context {
-            println!("Context: {}", p);
+        for c in &self.contexts {
+            println!("Context: {}", c);
         }
         if let Some(p) = &self.creation {
             println!("Creation: {}", p);
@@ -317,21 +795,360 @@ impl Todo {
         if let Some(p) = &self.completion {
             println!("Completion: {}", p);
         }
-        if !self.others.is_empty() {
-            for (k, v) in &self.others {
-                println!("{}: {}", k, v);
+        for (k, v) in &self.others {
+            println!("{}: {}", k, v);
+        }
+    }
+}
+
+const HEADER: [TokenKind; 4] = [
+    TokenKind::Completed,
+    TokenKind::Priority,
+    TokenKind::CompletionDate,
+    TokenKind::CreationDate,
+];
+
+/// The lenient mode reads the first date as the creation date, so the completion date has to
+/// come after it.
+const LENIENT_HEADER: [TokenKind; 4] = [
+    TokenKind::Completed,
+    TokenKind::Priority,
+    TokenKind::CreationDate,
+    TokenKind::CompletionDate,
+];
+
+/// Returns the order the header tokens are written in by the given mode.
+fn header(mode: ParseMode) -> &'static [TokenKind; 4] {
+    match mode {
+        ParseMode::Strict => &HEADER,
+        ParseMode::Lenient => &LENIENT_HEADER,
+    }
+}
+
+/// Updates a header token (completion marker, priority or a date).
+/// A missing header token is inserted right after the header tokens that come before it.
+fn sync_header(
+    tokens: &mut Vec<Token>,
+    header: &[TokenKind; 4],
+    kind: TokenKind,
+    value: Option<String>,
+) {
+    let order = header.iter().position(|k| *k == kind).unwrap_or(0);
+    let at = tokens
+        .iter()
+        .rposition(|t| header[..order].contains(&t.kind))
+        .map_or(0, |i| i + 1);
+    sync_token(tokens, kind, value, at);
+}
+
+/// Updates the tokens of a list field like the projects or the contexts.
+/// Tokens that are no longer in the list are removed and new values are inserted after the
+/// last token of the same kind, or at the end of the line if there is none.
+fn sync_list(tokens: &mut Vec<Token>, kind: TokenKind, prefix: char, values: &[String]) {
+    let mut missing = values.iter().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i].kind == kind {
+            match missing.iter().position(|v| tokens[i].text[1..] == ***v) {
+                Some(m) => {
+                    missing.remove(m);
+                }
+                None => {
+                    token::remove(tokens, i);
+                    continue;
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let mut at = tokens
+        .iter()
+        .rposition(|t| t.kind == kind)
+        .map_or(tokens.len(), |i| i + 1);
+    for v in missing {
+        at = token::insert(tokens, at, Token::new(kind, &format!("{}{}", prefix, v))) + 1;
+    }
+}
+
+fn sync_token(tokens: &mut Vec<Token>, kind: TokenKind, value: Option<String>, at: usize) {
+    match (token::find(tokens, kind), value) {
+        (Some(i), Some(v)) => {
+            if tokens[i].text != v {
+                tokens[i].text = v;
+            }
+        }
+        (Some(i), None) => token::remove(tokens, i),
+        (None, Some(v)) => {
+            token::insert(tokens, at, Token::new(kind, &v));
+        }
+        (None, None) => {}
+    }
+}
+
+/// Replaces the title words when the title no longer matches them.
+/// The new title takes the place of the first word of the old one.
+fn sync_title(tokens: &mut Vec<Token>, title: &str) {
+    let words = tokens
+        .iter()
+        .filter(|t| t.kind == TokenKind::Word)
+        .map(|t| t.text.as_str())
+        .collect::<Vec<_>>();
+    if words.join(" ") == title {
+        return;
+    }
+
+    let first = token::find(tokens, TokenKind::Word);
+    while let Some(i) = tokens.iter().rposition(|t| t.kind == TokenKind::Word) {
+        if Some(i) == first {
+            break;
+        }
+        token::remove(tokens, i);
+    }
+
+    let mut at = match first {
+        Some(i) => {
+            token::remove(tokens, i);
+            i.min(tokens.len())
+        }
+        None => tokens
+            .iter()
+            .rposition(|t| HEADER.contains(&t.kind))
+            .map_or(0, |i| i + 1),
+    };
+    for word in title.split_whitespace() {
+        at = token::insert(tokens, at, Token::new(TokenKind::Word, word)) + 1;
+    }
+}
+
+/// Updates the tag tokens from the tags.
+/// Tokens that still match a tag are kept as they are. A token whose tag changed value takes
+/// the new value in place, as long as no other token of the key still matches. Tokens of
+/// removed tags are dropped and new tags are inserted after the last tag token, or at the end
+/// of the line.
+fn sync_tags(tokens: &mut Vec<Token>, tags: &Tags) {
+    let mut missing = tags.iter().collect::<Vec<_>>();
+    let mut matched = Vec::new();
+    let mut unmatched = Vec::new();
+    for (i, t) in tokens.iter().enumerate() {
+        if t.kind != TokenKind::Tag {
+            continue;
+        }
+        let tag = t.text.split_once(':').unwrap_or_default();
+        match missing.iter().position(|m| *m == tag) {
+            Some(m) => {
+                missing.remove(m);
+                matched.push(tag.0.to_string());
+            }
+            None => unmatched.push(i),
+        }
+    }
+
+    let mut removed = Vec::new();
+    for i in unmatched {
+        let key = tokens[i].text.split_once(':').unwrap_or_default().0;
+        let renamed = missing
+            .iter()
+            .position(|(k, _)| *k == key && !matched.iter().any(|m| m == k));
+        match renamed {
+            Some(m) => {
+                let (k, v) = missing.remove(m);
+                tokens[i].text = format!("{}:{}", k, v);
             }
+            None => removed.push(i),
         }
     }
+    for i in removed.into_iter().rev() {
+        token::remove(tokens, i);
+    }
+
+    let mut at = tokens
+        .iter()
+        .rposition(|t| t.kind == TokenKind::Tag)
+        .map_or(tokens.len(), |i| i + 1);
+    for (k, v) in missing {
+        let tag = Token::new(TokenKind::Tag, &format!("{}:{}", k, v));
+        at = token::insert(tokens, at, tag) + 1;
+    }
+}
+
+/// Parses a date, returning an `InvalidDate` error pointing at the span when it fails.
+fn parse_date_at(s: &str, span: Range<usize>) -> Result<chrono::NaiveDate, TodoErr> {
+    parse_date(s).ok_or_else(|| TodoErr::InvalidDate {
+        value: s.to_string(),
+        at: Location::new(span),
+    })
+}
+
+/// Parses a `(A)` priority, returning an `InvalidPriority` error pointing at the span when it
+/// isn't `(A)` to `(Z)`.
+fn parse_priority_at(s: &str, span: Range<usize>) -> Result<Priority, TodoErr> {
+    s.parse().map_err(|e| match e {
+        TodoErr::InvalidPriority { value, .. } => TodoErr::InvalidPriority {
+            value,
+            at: Location::new(span),
+        },
+        e => e,
+    })
+}
+
+/// The place in the source an error was found at.
+/// The line is 1-based and the span is the byte range of the offending text in that line.
+/// A todo item parsed on its own is always on line 1, `TodoFile` fills in the real line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The 1-based line number.
+    pub line: usize,
+    /// The byte range in the line.
+    pub span: Range<usize>,
+}
+
+impl Location {
+    /// Creates a location on the first line.
+    pub fn new(span: Range<usize>) -> Self {
+        Location { line: 1, span }
+    }
 }
 
-/// An enum representing the various errors that can occur while parsing a todo item.
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.span.start + 1)
+    }
+}
+
+/// An enum representing the various errors that can occur while working with todo items.
 #[derive(Debug)]
 pub enum TodoErr {
     /// An error that occurs when the todo item has no title.
-    NoTitle,
-    /// An error that occurs when the regex parsing fails.
-    RegexParseErr,
+    NoTitle {
+        /// Where the todo item without a title is.
+        at: Location,
+    },
+    /// An error that occurs when a date doesn't exist, like `2024-13-45`.
+    InvalidDate {
+        /// The text of the date.
+        value: String,
+        /// Where the date is.
+        at: Location,
+    },
+    /// An error that occurs when a priority isn't `(A)` to `(Z)`.
+    InvalidPriority {
+        /// The text of the priority.
+        value: String,
+        /// Where the priority is.
+        at: Location,
+    },
+    /// An error that occurs when a `rec:` tag isn't in the format `1w` or `+1w`.
+    InvalidRecurrence {
+        /// The value of the tag.
+        value: String,
+        /// Where the value is.
+        at: Location,
+    },
+    /// An error that occurs when a filter query doesn't parse, see the `query` module.
+    InvalidQuery {
+        /// What is wrong with the query.
+        reason: String,
+        /// Where in the query the problem is.
+        at: Location,
+    },
+    /// An error that occurs when a character that can't join the words of a project or context
+    /// name is used as the separator of hierarchical names, see `TodoFile::set_separator`.
+    InvalidSeparator(char),
+    /// An error that occurs when a regex query can't be compiled or run.
+    RegexParseErr(String),
+    /// An error that occurs when a todo item is built from fields that don't fit together,
+    /// like a completion date on a todo item that isn't completed.
+    InvalidTodo(String),
+    /// An error that occurs when reading or writing a file.
+    Io(std::io::Error),
+    /// An error that occurs when converting from or to json.
+    Json(serde_json::Error),
+}
+
+impl TodoErr {
+    /// Returns the location of a parse error.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            TodoErr::NoTitle { at }
+            | TodoErr::InvalidDate { at, .. }
+            | TodoErr::InvalidPriority { at, .. }
+            | TodoErr::InvalidRecurrence { at, .. }
+            | TodoErr::InvalidQuery { at, .. } => Some(at),
+            _ => None,
+        }
+    }
+
+    /// Returns the 1-based line number of a parse error.
+    pub fn line(&self) -> Option<usize> {
+        self.location().map(|l| l.line)
+    }
+
+    /// Returns the byte span of a parse error in its line.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.location().map(|l| l.span.clone())
+    }
+
+    /// Moves a parse error to the given 1-based line.
+    pub(crate) fn at_line(mut self, line: usize) -> Self {
+        if let TodoErr::NoTitle { at }
+        | TodoErr::InvalidDate { at, .. }
+        | TodoErr::InvalidPriority { at, .. }
+        | TodoErr::InvalidRecurrence { at, .. }
+        | TodoErr::InvalidQuery { at, .. } = &mut self
+        {
+            at.line = line;
+        }
+        self
+    }
+}
+
+impl Display for TodoErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TodoErr::NoTitle { at } => write!(f, "{}: todo item has no title", at),
+            TodoErr::InvalidDate { value, at } => write!(f, "{}: invalid date `{}`", at, value),
+            TodoErr::InvalidPriority { value, at } => {
+                write!(f, "{}: invalid priority `{}`", at, value)
+            }
+            TodoErr::InvalidRecurrence { value, at } => {
+                write!(f, "{}: invalid recurrence `{}`", at, value)
+            }
+            TodoErr::InvalidQuery { reason, at } => write!(f, "{}: invalid query: {}", at, reason),
+            TodoErr::InvalidSeparator(c) => write!(
+                f,
+                "`{}` can't separate the levels of a name, use one of {:?}",
+                c,
+                token::NAME_SEPARATORS
+            ),
+            TodoErr::RegexParseErr(e) => write!(f, "regex error: {}", e),
+            TodoErr::InvalidTodo(e) => write!(f, "invalid todo item: {}", e),
+            TodoErr::Io(e) => write!(f, "io error: {}", e),
+            TodoErr::Json(e) => write!(f, "json error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TodoErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TodoErr::Io(e) => Some(e),
+            TodoErr::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TodoErr {
+    fn from(e: std::io::Error) -> Self {
+        TodoErr::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TodoErr {
+    fn from(e: serde_json::Error) -> Self {
+        TodoErr::Json(e)
+    }
 }
 
 impl FromStr for Todo {
@@ -350,43 +1167,27 @@ impl Default for Todo {
             priority: None,
             completion: None,
             creation: None,
-            project: None,
-            context: None,
-            others: HashMap::new(),
+            projects: Vec::new(),
+            contexts: Vec::new(),
+            others: Tags::new(),
             content: String::new(),
+            tokens: Vec::new(),
+            mode: ParseMode::default(),
+            id: id::next(),
         }
     }
 }
 
 impl Display for Todo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut s = String::new();
-        if self.completed {
-            s.push('x');
-        }
-        if let Some(p) = &self.priority {
-            s.push_str(&format!(" ({})", p));
-        }
-        if let Some(p) = &self.completion {
-            s.push_str(&format!(" {}", p));
-        }
-        if let Some(p) = &self.creation {
-            s.push_str(&format!(" {}", p));
-        }
-        s.push_str(&format!(" {}", self.title));
-        if let Some(p) = &self.project {
-            s.push_str(&format!(" +{}", p));
-        }
-        if let Some(p) = &self.context {
-            s.push_str(&format!(" @{}", p));
-        }
-        for (k, v) in &self.others {
-            s.push_str(&format!(" {}:{}", k, v));
-        }
-        write!(f, "{}", s)
+        write!(f, "{}", token::render(&self.sync_tokens()))
     }
 }
 
+pub(crate) fn parse_date(s: &str) -> Option<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
 #[cfg(test)]
 #[test]
 fn test_project_parse() {
@@ -403,16 +1204,14 @@ fn test_context_parse() {
 #[test]
 fn test_tags_parse() {
     let t = Todo::new("x (A) 2024-08-15 2024-09-20 Hello World +hello @wow due:123 some:word");
-    let mut map = HashMap::new();
-    map.insert("some".to_string(), "word".to_string());
-    map.insert("due".to_string(), "123".to_string());
-    assert_eq!(t.parse_tags().unwrap(), map);
+    let tags = Tags::from_iter([("due", "123"), ("some", "word")]);
+    assert_eq!(t.parse_tags().unwrap(), tags);
 }
 
 #[test]
 fn test_priority_parse() {
     let t = Todo::new("x (A) 2024-08-15 2024-09-20 Hello World +hello @wow due:123 some:word");
-    assert_eq!(t.parse_priority().unwrap(), Some("A".to_string()));
+    assert_eq!(t.parse_priority().unwrap(), Some(Priority::A));
 }
 
 #[test]
@@ -423,7 +1222,11 @@ fn test_title_parse() {
 
 #[test]
 fn test_dates_parse() {
-    let t = Todo::new("x (A) 2024-08-15 2024-09-20 Hello World +hello @wow due:123 some:word");
+    let t = Todo::parse_with(
+        "x (A) 2024-08-15 2024-09-20 Hello World +hello @wow due:123 some:word",
+        ParseMode::Lenient,
+    )
+    .unwrap();
     let dates = t.parse_dates().unwrap();
     assert_eq!(
         dates.0.unwrap(),
@@ -462,9 +1265,9 @@ fn test_smart_parse() {
     .unwrap();
     assert_eq!(
         t.creation.unwrap(),
-        chrono::NaiveDate::from_ymd_opt(2024, 8, 15).unwrap()
+        chrono::NaiveDate::from_ymd_opt(2024, 9, 20).unwrap()
     );
-    assert_eq!(t.priority.unwrap(), "A".to_string());
+    assert_eq!(t.priority.unwrap(), Priority::A);
 }
 
 #[test]
@@ -476,6 +1279,33 @@ fn test_toggle_status() {
     assert!(!t.completed);
 }
 
+#[test]
+fn test_toggle_status_round_trip() {
+    let today = chrono::Local::now().naive_local().date();
+    for line in [
+        "2024-08-01 Call mom",
+        "(B) Call mom +family",
+        "x 2024-09-01 2024-08-01 Call mom",
+        "x 2024-09-01 Call mom pri:C",
+    ] {
+        let mut t = Todo::parse(line).unwrap();
+        for _ in 0..2 {
+            t.toggle_status();
+            let reparsed = Todo::parse(&t.to_string()).unwrap();
+            assert_eq!(reparsed.completed, t.completed);
+            assert_eq!(reparsed.priority, t.priority);
+            assert_eq!(reparsed.creation, t.creation);
+            assert_eq!(reparsed.completion, t.completion);
+            assert_eq!(reparsed.title, "Call mom");
+        }
+    }
+
+    let mut t = Todo::parse("2024-08-01 Call mom").unwrap();
+    t.toggle_status();
+    assert_eq!(t.completion, Some(today));
+    assert_eq!(t.creation, chrono::NaiveDate::from_ymd_opt(2024, 8, 1));
+}
+
 #[test]
 fn test_display() {
     let mut t =
@@ -486,6 +1316,334 @@ fn test_display() {
     }
     assert_eq!(
         t.to_string(),
-        "x (A) 2024-09-20 2024-08-15 Hello World +hello @wow due:2021-08-15 some:word"
+        "x (A) 2024-08-15 2024-09-20 Hello World +hello @wow due:2021-08-15 some:word"
     );
 }
+
+#[test]
+fn test_display_round_trip() {
+    let line = " (B)  Call mom +family  about dinner due:2024-09-01 @phone +home";
+    let t = Todo::parse(line).unwrap();
+    assert_eq!(t.to_string(), line);
+}
+
+#[test]
+fn test_display_edit_keeps_order() {
+    let mut t = Todo::parse("(B) Call mom +family about dinner @phone due:2024-09-01").unwrap();
+    t.priority = Some(Priority::A);
+    t.contexts.clear();
+    t.others.set("due", "2024-09-02");
+    assert_eq!(
+        t.to_string(),
+        "(A) Call mom +family about dinner due:2024-09-02"
+    );
+
+    t.title = "Call dad".to_string();
+    t.completed = true;
+    assert_eq!(t.to_string(), "x (A) Call dad +family due:2024-09-02");
+}
+
+#[test]
+fn test_display_from_fields() {
+    let t = Todo {
+        title: "Hello World".to_string(),
+        priority: Some(Priority::A),
+        creation: chrono::NaiveDate::from_ymd_opt(2024, 8, 15),
+        projects: vec!["hello".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(t.to_string(), "(A) 2024-08-15 Hello World +hello");
+}
+
+#[test]
+fn test_projects_contexts_parse() {
+    let t = Todo::parse("Plan the quarter +work +q3 @phone @office").unwrap();
+    assert_eq!(t.projects, vec!["work", "q3"]);
+    assert_eq!(t.contexts, vec!["phone", "office"]);
+    assert_eq!(t.parse_projects().unwrap(), vec!["work", "q3"]);
+    assert_eq!(t.parse_contexts().unwrap(), vec!["phone", "office"]);
+}
+
+#[test]
+fn test_add_remove_projects() {
+    let mut t = Todo::parse("Plan the quarter +work +q3 @phone @office").unwrap();
+    t.add_project("review").unwrap();
+    t.add_project("work").unwrap();
+    assert!(t.remove_context("phone"));
+    assert!(!t.remove_context("home"));
+    t.add_context("home").unwrap();
+    assert_eq!(
+        t.to_string(),
+        "Plan the quarter +work +q3 +review @office @home"
+    );
+    assert!(t.remove_project("work"));
+    assert_eq!(t.to_string(), "Plan the quarter +q3 +review @office @home");
+}
+
+#[test]
+fn test_strict_parse() {
+    let t = Todo::parse("xylophone practice").unwrap();
+    assert!(!t.completed);
+    assert_eq!(t.title, "xylophone practice");
+
+    let t = Todo::parse("x 2024-09-20 2024-08-15 Meet (hello) team 2024-10-01").unwrap();
+    assert!(t.completed);
+    assert_eq!(t.completion, chrono::NaiveDate::from_ymd_opt(2024, 9, 20));
+    assert_eq!(t.creation, chrono::NaiveDate::from_ymd_opt(2024, 8, 15));
+    assert_eq!(t.priority, None);
+    assert_eq!(t.title, "Meet (hello) team 2024-10-01");
+    assert_eq!(t.parse_priority().unwrap(), None);
+    assert_eq!(t.parse_dates().unwrap(), (t.creation, t.completion));
+}
+
+#[test]
+fn test_lenient_parse() {
+    let t = Todo::parse_with("xylophone practice", ParseMode::Lenient).unwrap();
+    assert!(t.completed);
+    assert_eq!(t.title, "ylophone practice");
+    assert_eq!(t.to_string(), "xylophone practice");
+
+    let t = Todo::parse_with("Meet (B) team", ParseMode::Lenient).unwrap();
+    assert_eq!(t.priority, Priority::new('B'));
+    assert_eq!(t.title, "Meet team");
+
+    let t = Todo::parse_with("Meet (hello) team", ParseMode::Lenient).unwrap();
+    assert_eq!(t.priority, None);
+    assert_eq!(t.title, "Meet (hello) team");
+
+    let t = Todo::parse_with(
+        "x (A) 2024-08-15 2024-09-20 Hello World +hello @wow due:2021-08-15 some:word",
+        ParseMode::Lenient,
+    )
+    .unwrap();
+    assert_eq!(t.creation, chrono::NaiveDate::from_ymd_opt(2024, 8, 15));
+    assert_eq!(t.completion, chrono::NaiveDate::from_ymd_opt(2024, 9, 20));
+}
+
+#[test]
+fn test_parse_errors() {
+    match Todo::parse("2024-13-45 Broken date") {
+        Err(TodoErr::InvalidDate { value, at }) => {
+            assert_eq!(value, "2024-13-45");
+            assert_eq!(at, Location::new(0..10));
+        }
+        e => panic!("unexpected {:?}", e),
+    }
+
+    // a lowercase priority is a part of the title, like the todo.txt format says
+    let t = Todo::parse("(a) Call mom").unwrap();
+    assert_eq!(t.priority, None);
+    assert_eq!(t.title, "(a) Call mom");
+
+    let e = Todo::parse("x +project").unwrap_err();
+    assert!(matches!(e, TodoErr::NoTitle { .. }));
+    assert_eq!(e.to_string(), "line 1, column 1: todo item has no title");
+
+    let t = Todo::new("Pay rent due:2024-02-30");
+    let e = t.parse_due().unwrap_err();
+    assert_eq!(e.span(), Some(13..23));
+}
+
+#[test]
+fn test_duplicate_tags() {
+    let mut t = Todo::parse("Deploy dep:3 due:2024-09-01 dep:7").unwrap();
+    assert_eq!(t.others.get_all("dep").collect::<Vec<_>>(), vec!["3", "7"]);
+    assert_eq!(t.to_string(), "Deploy dep:3 due:2024-09-01 dep:7");
+
+    t.others.append("dep", "9");
+    assert_eq!(t.to_string(), "Deploy dep:3 due:2024-09-01 dep:7 dep:9");
+    t.others.remove_value("dep", "3");
+    t.others.set("due", "2024-09-02");
+    assert_eq!(t.to_string(), "Deploy due:2024-09-02 dep:7 dep:9");
+    t.others.remove("dep");
+    assert_eq!(t.to_string(), "Deploy due:2024-09-02");
+}
+
+#[test]
+fn test_complete_reopen() {
+    let on = chrono::NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+    let mut t = Todo::parse("(A) 2024-08-01 Call mom +family").unwrap();
+    t.complete(on);
+    assert!(t.completed);
+    assert_eq!(t.priority, None);
+    assert_eq!(
+        t.to_string(),
+        "x 2024-09-01 2024-08-01 Call mom +family pri:A"
+    );
+
+    let reparsed = Todo::parse(&t.to_string()).unwrap();
+    assert_eq!(reparsed.completion, Some(on));
+    assert_eq!(
+        reparsed.creation,
+        chrono::NaiveDate::from_ymd_opt(2024, 8, 1)
+    );
+
+    t.reopen();
+    assert!(!t.completed);
+    assert_eq!(t.completion, None);
+    assert_eq!(t.to_string(), "(A) 2024-08-01 Call mom +family");
+}
+
+#[test]
+fn test_complete_without_creation() {
+    let on = chrono::NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+    let mut t = Todo::parse("Call mom").unwrap();
+    t.complete(on);
+    assert_eq!(t.to_string(), "x 2024-09-01 Call mom");
+    let reparsed = Todo::parse(&t.to_string()).unwrap();
+    assert_eq!((reparsed.creation, reparsed.completion), (None, Some(on)));
+    t.reopen();
+    assert_eq!(t.to_string(), "Call mom");
+}
+
+#[test]
+fn test_complete_reopen_lenient() {
+    let on = chrono::NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+    let created = chrono::NaiveDate::from_ymd_opt(2024, 8, 1);
+    let mut t = Todo::parse_with("(A) Call mom 2024-08-01 +family", ParseMode::Lenient).unwrap();
+    t.complete(on);
+    assert_eq!(
+        t.to_string(),
+        "x Call mom 2024-08-01 2024-09-01 +family pri:A"
+    );
+    let reparsed = Todo::parse_with(&t.to_string(), ParseMode::Lenient).unwrap();
+    assert_eq!(
+        (reparsed.creation, reparsed.completion),
+        (created, Some(on))
+    );
+    assert_eq!(reparsed.title, t.title);
+    t.reopen();
+    assert_eq!(t.to_string(), "(A) Call mom 2024-08-01 +family");
+
+    let mut t = Todo::parse_with("Call mom", ParseMode::Lenient).unwrap();
+    t.complete(on);
+    assert_eq!(t.to_string(), "x Call mom");
+    let reparsed = Todo::parse_with(&t.to_string(), ParseMode::Lenient).unwrap();
+    assert_eq!((reparsed.creation, reparsed.completion), (None, None));
+    assert!(reparsed.completed);
+}
+
+#[test]
+fn test_threshold_parse() {
+    let t = Todo::parse("Renew passport t:2024-10-01 due:2024-11-01").unwrap();
+    let threshold = chrono::NaiveDate::from_ymd_opt(2024, 10, 1).unwrap();
+    assert_eq!(t.parse_threshold().unwrap(), Some(threshold));
+    assert!(!t.is_visible_on(threshold.pred_opt().unwrap()));
+    assert!(t.is_visible_on(threshold));
+
+    let t = Todo::parse("Broken t:2024-02-30").unwrap();
+    assert!(t.parse_threshold().is_err());
+    assert!(t.is_visible_on(threshold));
+}
+
+#[test]
+fn test_next_recurrence() {
+    let on = chrono::NaiveDate::from_ymd_opt(2024, 9, 3).unwrap();
+    let t = Todo::parse("(A) 2024-08-01 Water plants rec:1w due:2024-09-01 t:2024-08-30").unwrap();
+    let next = t.next_recurrence(on).unwrap().unwrap();
+    assert_eq!(
+        next.to_string(),
+        "(A) 2024-09-03 Water plants rec:1w due:2024-09-10 t:2024-09-08"
+    );
+
+    let t = Todo::parse("Pay rent rec:+1m due:2024-09-01 t:2024-08-25").unwrap();
+    let next = t.next_recurrence(on).unwrap().unwrap();
+    assert_eq!(
+        next.to_string(),
+        "Pay rent rec:+1m due:2024-10-01 t:2024-09-25"
+    );
+
+    let t = Todo::parse("Stretch rec:2d").unwrap();
+    let next = t.next_recurrence(on).unwrap().unwrap();
+    assert_eq!(next.to_string(), "Stretch rec:2d due:2024-09-05");
+
+    assert!(Todo::parse("Once")
+        .unwrap()
+        .next_recurrence(on)
+        .unwrap()
+        .is_none());
+    let e = Todo::parse("Bad rec:often").unwrap().next_recurrence(on);
+    assert!(matches!(e, Err(TodoErr::InvalidRecurrence { .. })));
+}
+
+#[test]
+fn test_relative_dates() {
+    let today = chrono::NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+    let mut t = Todo::parse("Call mom due:tomorrow t:today dep:fri").unwrap();
+    assert!(t.parse_due().is_err());
+    assert_eq!(
+        t.parse_due_on(today).unwrap(),
+        chrono::NaiveDate::from_ymd_opt(2024, 9, 3)
+    );
+    assert!(t.resolve_dates(today));
+    assert_eq!(
+        t.to_string(),
+        "Call mom due:2024-09-03 t:2024-09-02 dep:fri"
+    );
+    assert!(!t.resolve_dates(today));
+
+    let t = Todo::smart_parse_on("(B) Pay rent due:eom", today).unwrap();
+    assert_eq!(t.to_string(), "(B) 2024-09-02 Pay rent due:2024-09-30");
+}
+
+#[test]
+fn test_setters_sync_content() {
+    let mut t = Todo::parse("(B) Call mom +family @phone due:2024-09-01").unwrap();
+    t.set_title("Call dad").unwrap();
+    t.set_priority(Some(Priority::A));
+    t.set_due(chrono::NaiveDate::from_ymd_opt(2024, 9, 2));
+    t.set_threshold(chrono::NaiveDate::from_ymd_opt(2024, 8, 30));
+    t.remove_context("phone");
+    t.complete(chrono::NaiveDate::from_ymd_opt(2024, 9, 1).unwrap());
+    assert_eq!(
+        t.content,
+        "x 2024-09-01 Call dad +family due:2024-09-02 t:2024-08-30 pri:A"
+    );
+    assert_eq!(t.parse_title().unwrap(), "Call dad");
+    assert_eq!(t.parse_context().unwrap(), None);
+    assert_eq!(t.to_string(), t.content);
+
+    t.set_due(None);
+    assert_eq!(t.remove_tag("t"), vec!["2024-08-30"]);
+    t.set_completed(false);
+    assert_eq!(t.content, "(A) Call dad +family");
+
+    t.title = "Call mom".to_string();
+    assert_eq!(t.content, "(A) Call dad +family");
+    t.sync();
+    assert_eq!(t.content, "(A) Call mom +family");
+}
+
+#[test]
+fn test_setters_validate() {
+    let mut t = Todo::parse("(A) Call mom +x").unwrap();
+    assert!(matches!(
+        t.set_title("Call +mom"),
+        Err(TodoErr::InvalidTodo(_))
+    ));
+    assert!(matches!(t.set_title(" "), Err(TodoErr::NoTitle { .. })));
+    assert!(matches!(
+        t.add_project("two words"),
+        Err(TodoErr::InvalidTodo(_))
+    ));
+    assert!(matches!(
+        t.add_context("@home"),
+        Err(TodoErr::InvalidTodo(_))
+    ));
+    assert!(matches!(
+        t.set_tag("due", "2024-01-01 extra"),
+        Err(TodoErr::InvalidTodo(_))
+    ));
+    assert!(matches!(
+        t.set_tag("10", "30"),
+        Err(TodoErr::InvalidTodo(_))
+    ));
+    assert_eq!(t.content, "(A) Call mom +x");
+
+    t.set_title("Call  dad").unwrap();
+    t.set_tag("due", "2024-01-01").unwrap();
+    assert_eq!(t.title, "Call dad");
+    let again = Todo::parse(&t.content).unwrap();
+    assert_eq!(again.title, t.title);
+    assert_eq!(again.others, t.others);
+}
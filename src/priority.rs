@@ -0,0 +1,125 @@
+//! # Priority
+//!
+//! The priority of a todo item, written as `(A)` to `(Z)` at the start of the line.
+//! `A` is the most important priority, so priorities compare the same way they sort in a
+//! todo.txt file: `(A)` comes before `(B)`.
+//!
+//! ```rust
+//! use libdonow::priority::Priority;
+//!
+//! let p: Priority = "(B)".parse().unwrap();
+//! assert!(Priority::A < p);
+//! assert_eq!(p.raise(), Priority::A);
+//! assert_eq!(p.lower().to_string(), "C");
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::parser::{Location, TodoErr};
+
+/// A todo.txt priority from `A` to `Z`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct Priority(u8);
+
+impl Priority {
+    /// The most important priority.
+    pub const A: Priority = Priority(b'A');
+    /// The least important priority.
+    pub const Z: Priority = Priority(b'Z');
+
+    /// Creates a priority from its letter.
+    /// Returns `None` if the letter isn't `A` to `Z`.
+    pub fn new(letter: char) -> Option<Self> {
+        letter
+            .is_ascii_uppercase()
+            .then_some(Priority(letter as u8))
+    }
+
+    /// Returns the letter of the priority.
+    pub fn letter(&self) -> char {
+        self.0 as char
+    }
+
+    /// Returns the next more important priority, like `B` for `C`.
+    /// `A` stays `A`.
+    pub fn raise(&self) -> Self {
+        Priority(self.0.saturating_sub(1).max(b'A'))
+    }
+
+    /// Returns the next less important priority, like `D` for `C`.
+    /// `Z` stays `Z`.
+    pub fn lower(&self) -> Self {
+        Priority((self.0 + 1).min(b'Z'))
+    }
+}
+
+impl FromStr for Priority {
+    type Err = TodoErr;
+
+    /// Parses a priority from its letter, with or without the parentheses, like `A` or `(A)`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let letter = s
+            .strip_prefix('(')
+            .and_then(|s| s.strip_suffix(')'))
+            .unwrap_or(s);
+        let mut chars = letter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Priority::new(c),
+            _ => None,
+        }
+        .ok_or_else(|| TodoErr::InvalidPriority {
+            value: s.to_string(),
+            at: Location::new(0..s.len()),
+        })
+    }
+}
+
+impl TryFrom<String> for Priority {
+    type Error = TodoErr;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Priority> for String {
+    fn from(p: Priority) -> Self {
+        p.to_string()
+    }
+}
+
+impl Display for Priority {
+    /// Writes the letter of the priority without the parentheses.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_priority_parse() {
+    assert_eq!("A".parse::<Priority>().unwrap(), Priority::A);
+    assert_eq!("(Z)".parse::<Priority>().unwrap(), Priority::Z);
+    assert_eq!(Priority::new('C').unwrap().to_string(), "C");
+    assert!("(hello)".parse::<Priority>().is_err());
+    assert!("a".parse::<Priority>().is_err());
+    assert!("".parse::<Priority>().is_err());
+    assert!(Priority::new('1').is_none());
+}
+
+#[test]
+fn test_priority_order() {
+    let b = Priority::new('B').unwrap();
+    assert!(Priority::A < b && b < Priority::Z);
+    assert_eq!(b.raise(), Priority::A);
+    assert_eq!(Priority::A.raise(), Priority::A);
+    assert_eq!(b.lower(), Priority::new('C').unwrap());
+    assert_eq!(Priority::Z.lower(), Priority::Z);
+
+    let mut ps = vec![Some(b), None, Some(Priority::A)];
+    ps.sort_by_key(|p| (p.is_none(), *p));
+    assert_eq!(ps, vec![Some(Priority::A), Some(b), None]);
+}
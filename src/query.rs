@@ -0,0 +1,544 @@
+//! # Query
+//!
+//! A small query language for picking out todo items, compiled into a reusable `Filter`.
+//! Instead of chaining `get_project`, `get_context` and `search`, which each clone the todo
+//! items they match, a filter combines the conditions and runs once over `TodoFile::todos`
+//! with `TodoFile::filter` or `TodoFile::filter_indices`.
+//!
+//! A query is a list of terms that all have to match:
+//!
+//! - `+work` and `@phone` match a project or a context.
+//! - `pri:A` matches a priority and `pri:A-C` a range of them.
+//! - `done` matches completed todo items.
+//! - `due<2024-09-01` compares a date with `<`, `<=`, `=`, `>=` or `>`. The dates are `due`,
+//!   `t`, `created` and `completed`, and relative dates like `tomorrow` work as well, see the
+//!   `dates` module. `due:2024-09-01` is the same as `due=2024-09-01`.
+//! - `key:value` matches any other tag.
+//! - `"some text"` or a plain word matches the text of the todo item.
+//!
+//! Terms are combined with `AND`, which is implied between terms, and `OR`, which binds
+//! looser. `NOT` or a leading `-` negates a term, and parentheses group terms. `NOT`s and
+//! parentheses can be nested up to `MAX_DEPTH` levels deep.
+//!
+//! ```rust
+//! use libdonow::{file::TodoFile, query::Filter};
+//!
+//! let file = TodoFile::from_string(
+//!     "(A) Call mom @phone due:2024-08-30\n(D) Call the bank +work @phone\nx Pay rent +home\n",
+//! );
+//! let filter: Filter = "@phone (pri:A-C OR +work) -done".parse().unwrap();
+//! assert_eq!(file.filter_indices(&filter), vec![0, 1]);
+//! let filter: Filter = "due<2024-09-01 OR \"rent\"".parse().unwrap();
+//! assert_eq!(file.filter(&filter).len(), 2);
+//! ```
+
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+use crate::{
+    dates,
+    file::TodoFile,
+    parallel,
+    parser::{Location, Todo, TodoErr},
+    priority::Priority,
+};
+
+/// A date of a todo item a query can compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateField {
+    /// The `due:` tag.
+    Due,
+    /// The `t:` tag.
+    Threshold,
+    /// The creation date.
+    Created,
+    /// The completion date.
+    Completed,
+}
+
+/// A comparison of a date in a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `=`
+    Eq,
+    /// `>=`
+    Ge,
+    /// `>`
+    Gt,
+}
+
+/// A parsed query. See the module documentation for the syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// All of the expressions match. Matches everything when empty.
+    And(Vec<Expr>),
+    /// Any of the expressions match.
+    Or(Vec<Expr>),
+    /// The expression doesn't match.
+    Not(Box<Expr>),
+    /// The todo item has the project.
+    Project(String),
+    /// The todo item has the context.
+    Context(String),
+    /// The priority of the todo item is in the inclusive range.
+    Priority(Priority, Priority),
+    /// The todo item is completed.
+    Done,
+    /// The date of the todo item compares to the given date. Todo items without the date
+    /// don't match.
+    Date(DateField, Cmp, NaiveDate),
+    /// The todo item has the `key:value` tag.
+    Tag(String, String),
+    /// The content of the todo item contains the text.
+    Text(String),
+}
+
+/// A compiled query that can be run over todo items any number of times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    /// The parsed query.
+    pub expr: Expr,
+    /// The date relative dates in the todo items are resolved against.
+    pub today: NaiveDate,
+}
+
+impl Filter {
+    /// Parses a query, resolving relative dates against the local date.
+    /// Returns a `TodoErr::InvalidQuery` error for a query that doesn't parse, and a
+    /// `TodoErr::InvalidDate` or `TodoErr::InvalidPriority` error for a term with a bad value.
+    /// The location of an error is the byte span in the query.
+    pub fn parse(query: &str) -> Result<Self, TodoErr> {
+        Filter::parse_on(query, chrono::Local::now().naive_local().date())
+    }
+
+    /// Parses a query, resolving relative dates against the given date.
+    pub fn parse_on(query: &str, today: NaiveDate) -> Result<Self, TodoErr> {
+        let mut parser = Parser {
+            tokens: lex(query)?,
+            pos: 0,
+            depth: 0,
+            today,
+            len: query.len(),
+        };
+        let expr = if parser.tokens.is_empty() {
+            Expr::And(Vec::new())
+        } else {
+            parser.or()?
+        };
+        if let Some(t) = parser.tokens.get(parser.pos) {
+            return Err(invalid("unmatched `)`", t.span.clone()));
+        }
+
+        Ok(Filter { expr, today })
+    }
+
+    /// Returns whether the todo item matches the query.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        self.eval(&self.expr, todo)
+    }
+
+    fn eval(&self, expr: &Expr, todo: &Todo) -> bool {
+        match expr {
+            Expr::And(exprs) => exprs.iter().all(|e| self.eval(e, todo)),
+            Expr::Or(exprs) => exprs.iter().any(|e| self.eval(e, todo)),
+            Expr::Not(e) => !self.eval(e, todo),
+            Expr::Project(p) => todo.projects.iter().any(|x| x == p),
+            Expr::Context(c) => todo.contexts.iter().any(|x| x == c),
+            Expr::Priority(from, to) => todo.priority.is_some_and(|p| *from <= p && p <= *to),
+            Expr::Done => todo.completed,
+            Expr::Date(field, cmp, date) => {
+                let value = match field {
+                    DateField::Due => self.tag_date(todo, "due"),
+                    DateField::Threshold => self.tag_date(todo, "t"),
+                    DateField::Created => todo.creation,
+                    DateField::Completed => todo.completion,
+                };
+                value.is_some_and(|v| match cmp {
+                    Cmp::Lt => v < *date,
+                    Cmp::Le => v <= *date,
+                    Cmp::Eq => v == *date,
+                    Cmp::Ge => v >= *date,
+                    Cmp::Gt => v > *date,
+                })
+            }
+            Expr::Tag(key, value) => todo.others.get_all(key).any(|v| v == value),
+            Expr::Text(text) => todo.content.contains(text.as_str()),
+        }
+    }
+
+    fn tag_date(&self, todo: &Todo, key: &str) -> Option<NaiveDate> {
+        dates::resolve(todo.others.get(key)?, self.today)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = TodoErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Filter::parse(s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Word(String),
+    Quoted(String),
+    Open,
+    Close,
+    Not,
+}
+
+struct Token {
+    kind: Kind,
+    span: std::ops::Range<usize>,
+}
+
+fn invalid(reason: &str, span: std::ops::Range<usize>) -> TodoErr {
+    TodoErr::InvalidQuery {
+        reason: reason.to_string(),
+        at: Location::new(span),
+    }
+}
+
+/// Splits a query into words, quoted text, parentheses and negations.
+fn lex(query: &str) -> Result<Vec<Token>, TodoErr> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Kind::Open,
+            ')' => Kind::Close,
+            '-' if chars.peek().is_some_and(|(_, n)| !n.is_whitespace()) => Kind::Not,
+            '"' => {
+                let text = match query[start + 1..].find('"') {
+                    Some(end) => &query[start + 1..start + 1 + end],
+                    None => return Err(invalid("unclosed `\"`", start..query.len())),
+                };
+                while chars
+                    .next_if(|(i, _)| *i <= start + 1 + text.len())
+                    .is_some()
+                {}
+                tokens.push(Token {
+                    kind: Kind::Quoted(text.to_string()),
+                    span: start..start + text.len() + 2,
+                });
+                continue;
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, n)) =
+                    chars.next_if(|(_, n)| !n.is_whitespace() && *n != '(' && *n != ')')
+                {
+                    end = i + n.len_utf8();
+                }
+                tokens.push(Token {
+                    kind: Kind::Word(query[start..end].to_string()),
+                    span: start..end,
+                });
+                continue;
+            }
+        };
+        tokens.push(Token {
+            kind,
+            span: start..start + 1,
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// The deepest a query can nest `NOT`s and parentheses, so that a query typed in by a user
+/// can't overflow the stack of the parser.
+pub const MAX_DEPTH: usize = 64;
+
+/// A recursive descent parser over the tokens of a query.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// The number of `NOT`s and parentheses around the current token.
+    depth: usize,
+    today: NaiveDate,
+    len: usize,
+}
+
+impl Parser {
+    fn peek_word(&self, word: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token { kind: Kind::Word(w), .. }) if w == word)
+    }
+
+    /// `and ("OR" and)*`
+    fn or(&mut self) -> Result<Expr, TodoErr> {
+        let mut exprs = vec![self.and()?];
+        while self.peek_word("OR") {
+            self.pos += 1;
+            exprs.push(self.and()?);
+        }
+
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    /// `unary (["AND"] unary)*`
+    fn and(&mut self) -> Result<Expr, TodoErr> {
+        let mut exprs = vec![self.unary()?];
+        loop {
+            match self.tokens.get(self.pos) {
+                None
+                | Some(Token {
+                    kind: Kind::Close, ..
+                }) => break,
+                _ if self.peek_word("OR") => break,
+                _ if self.peek_word("AND") => {
+                    self.pos += 1;
+                    exprs.push(self.unary()?);
+                }
+                _ => exprs.push(self.unary()?),
+            }
+        }
+
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    /// `("NOT" | "-") unary | "(" or ")" | term`
+    fn unary(&mut self) -> Result<Expr, TodoErr> {
+        let Some(token) = self.tokens.get(self.pos) else {
+            return Err(invalid("expected a term", self.len..self.len));
+        };
+        let (kind, span) = (token.kind.clone(), token.span.clone());
+        self.pos += 1;
+
+        let nested =
+            matches!(&kind, Kind::Not | Kind::Open) || matches!(&kind, Kind::Word(w) if w == "NOT");
+        if nested && self.depth == MAX_DEPTH {
+            return Err(invalid("the query is nested too deeply", span));
+        }
+
+        match kind {
+            Kind::Not => self.not(),
+            Kind::Word(w) if w == "NOT" => self.not(),
+            Kind::Word(w) if w == "AND" || w == "OR" => {
+                Err(invalid(&format!("expected a term before `{}`", w), span))
+            }
+            Kind::Open => {
+                self.depth += 1;
+                let expr = self.or();
+                self.depth -= 1;
+                let expr = expr?;
+                match self.tokens.get(self.pos) {
+                    Some(Token {
+                        kind: Kind::Close, ..
+                    }) => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(invalid("unclosed `(`", span)),
+                }
+            }
+            Kind::Close => Err(invalid("expected a term before `)`", span)),
+            Kind::Quoted(text) => Ok(Expr::Text(text)),
+            Kind::Word(w) => self.term(&w, span),
+        }
+    }
+
+    /// The term after a `NOT`, negated.
+    fn not(&mut self) -> Result<Expr, TodoErr> {
+        self.depth += 1;
+        let expr = self.unary();
+        self.depth -= 1;
+        Ok(Expr::Not(Box::new(expr?)))
+    }
+
+    fn term(&self, w: &str, span: std::ops::Range<usize>) -> Result<Expr, TodoErr> {
+        if let Some(p) = w.strip_prefix('+').filter(|p| !p.is_empty()) {
+            return Ok(Expr::Project(p.to_string()));
+        }
+        if let Some(c) = w.strip_prefix('@').filter(|c| !c.is_empty()) {
+            return Ok(Expr::Context(c.to_string()));
+        }
+        if w == "done" {
+            return Ok(Expr::Done);
+        }
+        if let Some(range) = w.strip_prefix("pri:") {
+            let at = span.start + 4..span.end;
+            let (from, to) = range.split_once('-').unwrap_or((range, range));
+            let (from, to) = (priority(from, &at)?, priority(to, &at)?);
+            if from > to {
+                return Err(invalid(
+                    &format!("the range is reversed, use `pri:{}-{}`", to, from),
+                    at,
+                ));
+            }
+            return Ok(Expr::Priority(from, to));
+        }
+
+        let date_field = |key: &str| match key {
+            "due" => Some(DateField::Due),
+            "t" => Some(DateField::Threshold),
+            "created" => Some(DateField::Created),
+            "completed" => Some(DateField::Completed),
+            _ => None,
+        };
+        if let Some(i) = w.find(['<', '>', '=', ':']) {
+            let (key, rest) = w.split_at(i);
+            if let Some(field) = date_field(key) {
+                let (cmp, value) = [
+                    ("<=", Cmp::Le),
+                    (">=", Cmp::Ge),
+                    ("<", Cmp::Lt),
+                    (">", Cmp::Gt),
+                    ("=", Cmp::Eq),
+                    (":", Cmp::Eq),
+                ]
+                .iter()
+                .find_map(|(op, cmp)| rest.strip_prefix(op).map(|v| (*cmp, v)))
+                .unwrap_or((Cmp::Eq, rest));
+                let date =
+                    dates::resolve(value, self.today).ok_or_else(|| TodoErr::InvalidDate {
+                        value: value.to_string(),
+                        at: Location::new(span.end - value.len()..span.end),
+                    })?;
+                return Ok(Expr::Date(field, cmp, date));
+            }
+        }
+        if let Some((key, value)) = w.split_once(':') {
+            if !key.is_empty() && !value.is_empty() {
+                return Ok(Expr::Tag(key.to_string(), value.to_string()));
+            }
+        }
+
+        Ok(Expr::Text(w.to_string()))
+    }
+}
+
+fn priority(letter: &str, at: &std::ops::Range<usize>) -> Result<Priority, TodoErr> {
+    let mut chars = letter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Priority::new(c.to_ascii_uppercase()),
+        _ => None,
+    }
+    .ok_or_else(|| TodoErr::InvalidPriority {
+        value: letter.to_string(),
+        at: Location::new(at.clone()),
+    })
+}
+
+impl TodoFile {
+    /// Returns the todo items that match the filter, in order.
+    pub fn filter(&self, filter: &Filter) -> Vec<&Todo> {
+        self.filter_indices(filter)
+            .into_iter()
+            .map(|i| &self.todos[i])
+            .collect()
+    }
+
+    /// Returns the indices of the todo items that match the filter, in order.
+    pub fn filter_indices(&self, filter: &Filter) -> Vec<usize> {
+        parallel::map(&self.todos, |i, todo| filter.matches(todo).then_some(i))
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_query() {
+    let today = NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+    let f = Filter::parse_on("+work @phone pri:a-C due<tomorrow -done \"a b\"", today).unwrap();
+    assert_eq!(
+        f.expr,
+        Expr::And(vec![
+            Expr::Project("work".to_string()),
+            Expr::Context("phone".to_string()),
+            Expr::Priority(Priority::A, Priority::new('C').unwrap()),
+            Expr::Date(DateField::Due, Cmp::Lt, today.succ_opt().unwrap()),
+            Expr::Not(Box::new(Expr::Done)),
+            Expr::Text("a b".to_string()),
+        ])
+    );
+
+    let f = Filter::parse_on("a OR NOT (b c) OR x:y", today).unwrap();
+    assert_eq!(
+        f.expr,
+        Expr::Or(vec![
+            Expr::Text("a".to_string()),
+            Expr::Not(Box::new(Expr::And(vec![
+                Expr::Text("b".to_string()),
+                Expr::Text("c".to_string()),
+            ]))),
+            Expr::Tag("x".to_string(), "y".to_string()),
+        ])
+    );
+    assert_eq!(
+        Filter::parse_on("", today).unwrap().expr,
+        Expr::And(Vec::new())
+    );
+}
+
+#[test]
+fn test_parse_query_errors() {
+    let span = |q: &str| Filter::parse(q).unwrap_err().span();
+    assert_eq!(span("(a b"), Some(0..1));
+    assert_eq!(span("a b)"), Some(3..4));
+    assert_eq!(span("a OR"), Some(4..4));
+    assert_eq!(span("\"abc"), Some(0..4));
+    assert_eq!(span("x due>2024-13-01"), Some(6..16));
+    assert!(matches!(
+        Filter::parse("pri:AB"),
+        Err(TodoErr::InvalidPriority { .. })
+    ));
+    assert_eq!(span("x pri:C-A"), Some(6..9));
+
+    let deep = |open: &str, close: &str, n: usize| {
+        Filter::parse(&format!("{}a{}", open.repeat(n), close.repeat(n)))
+    };
+    assert!(deep("NOT ", "", MAX_DEPTH).is_ok());
+    assert!(deep("(", ")", MAX_DEPTH).is_ok());
+    assert!(matches!(
+        deep("NOT ", "", 100_000),
+        Err(TodoErr::InvalidQuery { .. })
+    ));
+    assert!(matches!(
+        deep("-(", ")", 100_000),
+        Err(TodoErr::InvalidQuery { .. })
+    ));
+}
+
+#[test]
+fn test_filter() {
+    let file = TodoFile::from_string(
+        "(A) Call mom @phone due:2024-08-30\n\
+         2024-08-01 Plan trip +travel t:2024-09-10\n\
+         x 2024-09-02 2024-08-20 Pay rent +home due:2024-09-01\n\
+         (C) Fix bug +work.backend url:x\n",
+    );
+    let today = NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+    let indices = |q: &str| file.filter_indices(&Filter::parse_on(q, today).unwrap());
+    assert_eq!(indices("due<=today"), vec![0, 2]);
+    assert_eq!(indices("due<=today -done"), vec![0]);
+    assert_eq!(
+        indices("created<2024-08-15 OR completed=2024-09-02"),
+        vec![1, 2]
+    );
+    assert_eq!(indices("t>2024-09-01"), vec![1]);
+    assert_eq!(indices("pri:A-B OR url:x"), vec![0, 3]);
+    assert_eq!(indices("+work.backend NOT @phone"), vec![3]);
+    assert_eq!(indices(""), vec![0, 1, 2, 3]);
+    assert_eq!(
+        file.filter(&Filter::parse_on("rent", today).unwrap())[0].title,
+        "Pay rent"
+    );
+}
@@ -0,0 +1,159 @@
+//! # Recurrence
+//!
+//! Support for the community `rec:` extension of the todo.txt format.
+//! A recurring todo item has a tag like `rec:1w` or `rec:+1m`. When it is completed, a fresh
+//! copy is added with its `due:` and `t:` dates moved forward.
+//!
+//! - `rec:1w` recurs from the completion date: the next copy is due a week after the todo item
+//!   was completed.
+//! - `rec:+1m` is strict and recurs from the due date: the next copy is due a month after the
+//!   old due date, no matter when the todo item was completed.
+//!
+//! The supported units are `d` (days), `b` (business days), `w` (weeks), `m` (months) and `y`
+//! (years).
+//!
+//! ```rust
+//! use libdonow::recur::Recurrence;
+//!
+//! let rec: Recurrence = "+2w".parse().unwrap();
+//! assert!(rec.strict);
+//! let date = chrono::NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+//! assert_eq!(rec.advance(date), chrono::NaiveDate::from_ymd_opt(2024, 9, 15));
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{Datelike, Months, NaiveDate, Weekday};
+
+/// The unit of a `Recurrence`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Calendar days.
+    Day,
+    /// Days from Monday to Friday.
+    BusinessDay,
+    /// Weeks of seven days.
+    Week,
+    /// Calendar months, clamped to the end of shorter months.
+    Month,
+    /// Calendar years.
+    Year,
+}
+
+/// How often a todo item recurs, as written in a `rec:` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recurrence {
+    /// How many units to move forward.
+    pub amount: u32,
+    /// The unit to move forward in.
+    pub unit: Unit,
+    /// Whether the recurrence is strict (`rec:+1w`) and counts from the due date instead of
+    /// the completion date.
+    pub strict: bool,
+}
+
+impl Recurrence {
+    /// Moves the date forward by the recurrence.
+    /// Returns `None` if the date would go out of the range chrono supports.
+    pub fn advance(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self.unit {
+            Unit::Day => date.checked_add_days(chrono::Days::new(self.amount.into())),
+            Unit::Week => date.checked_add_days(chrono::Days::new(u64::from(self.amount) * 7)),
+            Unit::Month => date.checked_add_months(Months::new(self.amount)),
+            Unit::Year => date.checked_add_months(Months::new(self.amount.checked_mul(12)?)),
+            Unit::BusinessDay => {
+                let mut date = date;
+                let mut left = self.amount;
+                while left > 0 {
+                    date = date.succ_opt()?;
+                    if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+                        left -= 1;
+                    }
+                }
+                Some(date)
+            }
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = ();
+
+    /// Parses the value of a `rec:` tag, like `1w` or `+3d`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (strict, s) = match s.strip_prefix('+') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let unit = match s.chars().last().ok_or(())? {
+            'd' => Unit::Day,
+            'b' => Unit::BusinessDay,
+            'w' => Unit::Week,
+            'm' => Unit::Month,
+            'y' => Unit::Year,
+            _ => return Err(()),
+        };
+        let amount = &s[..s.len() - 1];
+        if amount.is_empty() || !amount.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(());
+        }
+
+        Ok(Recurrence {
+            amount: amount.parse().map_err(|_| ())?,
+            unit,
+            strict,
+        })
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self.unit {
+            Unit::Day => 'd',
+            Unit::BusinessDay => 'b',
+            Unit::Week => 'w',
+            Unit::Month => 'm',
+            Unit::Year => 'y',
+        };
+        if self.strict {
+            write!(f, "+")?;
+        }
+        write!(f, "{}{}", self.amount, unit)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_recurrence_parse() {
+    let rec: Recurrence = "1w".parse().unwrap();
+    assert_eq!(
+        rec,
+        Recurrence {
+            amount: 1,
+            unit: Unit::Week,
+            strict: false
+        }
+    );
+    assert_eq!(rec.to_string(), "1w");
+    assert_eq!("+12m".parse::<Recurrence>().unwrap().to_string(), "+12m");
+    assert!("w".parse::<Recurrence>().is_err());
+    assert!("1x".parse::<Recurrence>().is_err());
+    assert!("+-1d".parse::<Recurrence>().is_err());
+}
+
+#[test]
+fn test_recurrence_advance() {
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    let rec = |s: &str| s.parse::<Recurrence>().unwrap();
+    assert_eq!(rec("3d").advance(date(2024, 2, 27)), Some(date(2024, 3, 1)));
+    assert_eq!(
+        rec("1m").advance(date(2024, 1, 31)),
+        Some(date(2024, 2, 29))
+    );
+    assert_eq!(
+        rec("1y").advance(date(2024, 2, 29)),
+        Some(date(2025, 2, 28))
+    );
+    // friday plus two business days is tuesday
+    assert_eq!(rec("2b").advance(date(2024, 8, 30)), Some(date(2024, 9, 3)));
+}
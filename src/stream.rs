@@ -0,0 +1,230 @@
+//! # Streaming
+//!
+//! Reading and writing todo.txt files one line at a time.
+//! `TodoFile` keeps the whole file in memory, which is wasteful for scans over big files like
+//! a done.txt archive of many years. A `TodoReader` parses the lines of any `BufRead` lazily
+//! as it is iterated and only holds on to the current line, and a `TodoWriter` writes todo
+//! items and raw lines out as they come.
+//!
+//! ```rust
+//! use libdonow::stream::{TodoReader, TodoWriter};
+//!
+//! let archive = "x 2024-09-01 Pay rent +home\n# old\nx 2024-09-02 Call mom\n";
+//! let mut writer = TodoWriter::new(Vec::new());
+//! for todo in TodoReader::new(archive.as_bytes()).todos() {
+//!     let todo = todo.expect("Failed to read the archive");
+//!     if todo.projects.iter().any(|p| p == "home") {
+//!         writer.write(&todo).expect("Failed to write");
+//!     }
+//! }
+//! assert_eq!(writer.into_inner(), b"x 2024-09-01 Pay rent +home\n");
+//! ```
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    file::{self, RawLine},
+    parser::{ParseMode, Todo},
+};
+
+/// A line read by a `TodoReader`.
+#[derive(Debug)]
+pub enum Line {
+    /// A line that parsed as a todo item.
+    Todo(Todo),
+    /// A blank line, a comment or a line that failed to parse.
+    /// Its position is the number of todo items read before it.
+    Raw(RawLine),
+}
+
+/// An iterator over the lines of a todo.txt file that parses them as it goes.
+///
+/// Every line is parsed the same way `TodoFile::load` parses it. A line that isn't valid UTF-8
+/// is reported as an `io::ErrorKind::InvalidData` error and skipped, the reader carries on
+/// with the next line. The reader stops after any other error.
+pub struct TodoReader<R> {
+    reader: R,
+    mode: ParseMode,
+    buf: Vec<u8>,
+    line: usize,
+    todos: usize,
+    done: bool,
+}
+
+impl<R: BufRead> TodoReader<R> {
+    /// Creates a reader that parses the lines with the default `ParseMode`.
+    pub fn new(reader: R) -> Self {
+        TodoReader::with_mode(reader, ParseMode::default())
+    }
+
+    /// Creates a reader that parses the lines with the given `ParseMode`.
+    pub fn with_mode(reader: R, mode: ParseMode) -> Self {
+        TodoReader {
+            reader,
+            mode,
+            buf: Vec::new(),
+            line: 0,
+            todos: 0,
+            done: false,
+        }
+    }
+
+    /// Returns the number of lines read so far, which is the 1-based number of the last line.
+    pub fn line_number(&self) -> usize {
+        self.line
+    }
+
+    /// Returns an iterator over only the todo items, skipping the raw lines.
+    pub fn todos(self) -> impl Iterator<Item = io::Result<Todo>> {
+        self.filter_map(|line| match line {
+            Ok(Line::Todo(todo)) => Some(Ok(todo)),
+            Ok(Line::Raw(_)) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+}
+
+impl TodoReader<BufReader<File>> {
+    /// Opens the file at the path for reading.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(TodoReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> Iterator for TodoReader<R> {
+    type Item = io::Result<Line>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        self.buf.clear();
+        match self.reader.read_until(b'\n', &mut self.buf) {
+            Ok(0) => {
+                self.done = true;
+                return None;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+
+        let i = self.line;
+        self.line += 1;
+        let line = match std::str::from_utf8(&self.buf) {
+            Ok(line) => line.trim_end_matches('\n').trim_end_matches('\r'),
+            Err(e) => {
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", i + 1, e),
+                )))
+            }
+        };
+
+        Some(Ok(match file::parse_line(i, line, self.mode) {
+            Ok(todo) => {
+                self.todos += 1;
+                Line::Todo(todo)
+            }
+            Err(kind) => Line::Raw(RawLine {
+                position: self.todos,
+                content: line.to_string(),
+                kind,
+            }),
+        }))
+    }
+}
+
+/// Writes todo items and raw lines one line at a time, the way `TodoFile::render` formats
+/// them.
+pub struct TodoWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TodoWriter<W> {
+    /// Creates a writer. Wrap the writer in a `BufWriter` unless it buffers already.
+    pub fn new(writer: W) -> Self {
+        TodoWriter { writer }
+    }
+
+    /// Writes a todo item as a line.
+    pub fn write(&mut self, todo: &Todo) -> io::Result<()> {
+        writeln!(self.writer, "{}", todo)
+    }
+
+    /// Writes a raw line as it was read.
+    pub fn write_raw(&mut self, raw: &RawLine) -> io::Result<()> {
+        writeln!(self.writer, "{}", raw.content)
+    }
+
+    /// Writes a line read by a `TodoReader`.
+    pub fn write_line(&mut self, line: &Line) -> io::Result<()> {
+        match line {
+            Line::Todo(todo) => self.write(todo),
+            Line::Raw(raw) => self.write_raw(raw),
+        }
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl TodoWriter<BufWriter<File>> {
+    /// Creates or truncates the file at the path for writing.
+    /// Unlike `TodoFile::save`, the file isn't replaced atomically. Call `flush` when done to
+    /// see the errors of the last write.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(TodoWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_read_write() {
+    let content = "(A) Call mom\r\n\n# chores\n2024-13-45 Broken date\nx Pay rent\n";
+    let mut reader = TodoReader::new(content.as_bytes());
+    let lines = reader.by_ref().collect::<io::Result<Vec<_>>>().unwrap();
+    assert_eq!(reader.line_number(), 5);
+    assert_eq!(lines.len(), 5);
+    assert!(matches!(
+        &lines[3],
+        Line::Raw(RawLine { position: 1, kind: file::RawKind::Invalid(e), .. }) if e.line() == Some(4)
+    ));
+
+    let mut writer = TodoWriter::new(Vec::new());
+    for line in &lines {
+        writer.write_line(line).unwrap();
+    }
+    let written = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!(written, content.replace("\r\n", "\n"));
+    assert_eq!(
+        written,
+        crate::file::TodoFile::from_string(content).render()
+    );
+}
+
+#[test]
+fn test_read_invalid_utf8() {
+    let content: &[u8] = b"Call mom\n\xff\xfe\nPay rent";
+    let lines = TodoReader::new(content).collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[1].as_ref().unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    assert!(matches!(&lines[2], Ok(Line::Todo(t)) if t.title == "Pay rent"));
+}
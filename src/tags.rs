@@ -0,0 +1,216 @@
+//! # Tags
+//!
+//! An ordered collection of the `key:value` tags of a todo item.
+//! Tags keep the order they were written in and a key can have more than one value, so a line
+//! like `Deploy dep:3 dep:7` keeps both of its dependencies and is always written back the
+//! same way.
+//!
+//! ```rust
+//! use libdonow::tags::Tags;
+//!
+//! let mut tags = Tags::new();
+//! tags.append("dep", "3");
+//! tags.append("dep", "7");
+//! tags.set("due", "2024-09-01");
+//! assert_eq!(tags.get_all("dep").collect::<Vec<_>>(), vec!["3", "7"]);
+//! assert_eq!(tags.to_string(), "dep:3 dep:7 due:2024-09-01");
+//! ```
+
+use std::fmt::Display;
+
+/// An ordered multimap of `key:value` tags.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Tags {
+    entries: Vec<(String, String)>,
+}
+
+impl Tags {
+    /// Creates an empty collection of tags.
+    pub fn new() -> Self {
+        Tags::default()
+    }
+
+    /// Returns the number of tags, counting every value of a key.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether there are no tags.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the tags in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the keys of the tags in order, a key with several values is returned once for
+    /// each value.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(k, _)| k.as_str())
+    }
+
+    /// Returns whether there is a tag with the key.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.iter().any(|(k, _)| k == key)
+    }
+
+    /// Returns the first value of the key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns all of the values of the key in order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Sets the key to a single value.
+    /// The first tag with the key keeps its place and takes the value, any other tags with
+    /// the key are removed. If there is no tag with the key, it is appended.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.entries.iter().position(|(k, _)| k == key) {
+            Some(i) => {
+                self.entries[i].1 = value.to_string();
+                let mut n = 0;
+                self.entries.retain(|(k, _)| {
+                    n += 1;
+                    n <= i + 1 || k != key
+                });
+            }
+            None => self.append(key, value),
+        }
+    }
+
+    /// Appends a tag, keeping any other values the key already has.
+    pub fn append(&mut self, key: &str, value: &str) {
+        self.entries.push((key.to_string(), value.to_string()));
+    }
+
+    /// Replaces the value of a single `key:value` tag, keeping its place.
+    /// Returns whether the tag was found.
+    pub fn replace_value(&mut self, key: &str, value: &str, new: &str) -> bool {
+        match self
+            .entries
+            .iter_mut()
+            .find(|(k, v)| k == key && v == value)
+        {
+            Some((_, v)) => {
+                *v = new.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes all of the tags with the key and returns their values.
+    pub fn remove(&mut self, key: &str) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.entries.retain(|(k, v)| {
+            if k == key {
+                removed.push(v.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        removed
+    }
+
+    /// Removes a single `key:value` tag.
+    /// Returns whether the tag was found.
+    pub fn remove_value(&mut self, key: &str, value: &str) -> bool {
+        match self
+            .entries
+            .iter()
+            .position(|(k, v)| k == key && v == value)
+        {
+            Some(i) => {
+                self.entries.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Tags {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Tags {
+            entries: iter
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Tags {
+    type Item = (&'a str, &'a str);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (String, String)>,
+        fn(&'a (String, String)) -> (&'a str, &'a str),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+impl Display for Tags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (k, v)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}:{}", k, v)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_set_keeps_place() {
+    let mut tags = Tags::from_iter([("dep", "3"), ("due", "2024-09-01"), ("dep", "7")]);
+    tags.set("dep", "5");
+    assert_eq!(tags.to_string(), "dep:5 due:2024-09-01");
+    tags.set("t", "2024-08-01");
+    assert_eq!(tags.to_string(), "dep:5 due:2024-09-01 t:2024-08-01");
+}
+
+#[test]
+fn test_remove() {
+    let mut tags = Tags::from_iter([("dep", "3"), ("due", "2024-09-01"), ("dep", "7")]);
+    assert!(tags.remove_value("dep", "7"));
+    assert!(!tags.remove_value("dep", "7"));
+    assert_eq!(tags.remove("dep"), vec!["3"]);
+    assert_eq!(tags.len(), 1);
+    assert!(!tags.contains_key("dep"));
+}
+
+#[test]
+fn test_replace_value() {
+    let mut tags = Tags::from_iter([("dep", "3"), ("due", "2024-09-01"), ("dep", "7")]);
+    assert!(tags.replace_value("dep", "7", "5"));
+    assert!(!tags.replace_value("dep", "7", "5"));
+    assert_eq!(tags.to_string(), "dep:3 due:2024-09-01 dep:5");
+}
+
+#[test]
+fn test_serde_keeps_order() {
+    let tags = Tags::from_iter([("dep", "3"), ("dep", "7")]);
+    let json = serde_json::to_value(&tags).unwrap();
+    assert_eq!(json, serde_json::json!([["dep", "3"], ["dep", "7"]]));
+    assert_eq!(serde_json::from_value::<Tags>(json).unwrap(), tags);
+}
@@ -0,0 +1,549 @@
+//! # Tokens
+//!
+//! A lossless, token based representation of a single todo.txt line.
+//! Every byte of the line belongs to exactly one token, including the whitespace between the
+//! words, so rendering the tokens of an untouched line gives back the exact same line.
+//!
+//! The `parser::Todo` struct keeps the tokens of the line it was parsed from and only rewrites
+//! the tokens of the fields that were changed. This way saving a file with `TodoFile::save`
+//! doesn't reorder projects, contexts or tags of lines that were never edited.
+//!
+//! The tokens are produced by the `Lexer`, a single pass lexer that borrows from the line
+//! and doesn't allocate, which makes it cheap to use for queries over big files.
+//!
+//! Projects, contexts and tags are only recognised as whole whitespace delimited words, so an
+//! email like `me@corp.com`, a sum like `a+b` or a url like `https://example.com` stay a part
+//! of the title.
+//!
+//! ```rust
+//! use libdonow::token;
+//!
+//! let tokens = token::tokenize("(A) Call mom  +family @phone");
+//! assert_eq!(token::render(&tokens), "(A) Call mom  +family @phone");
+//! ```
+
+use crate::parser::ParseMode;
+
+/// The kind of a token in a todo.txt line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A run of whitespace between two tokens.
+    Whitespace,
+    /// The `x` marking the todo item as completed.
+    Completed,
+    /// The priority in the format `(A)`.
+    Priority,
+    /// The completion date of the todo item.
+    CompletionDate,
+    /// The creation date of the todo item.
+    CreationDate,
+    /// A project in the format `+project`.
+    Project,
+    /// A context in the format `@context`.
+    Context,
+    /// A tag in the format `key:value`.
+    Tag,
+    /// A word that is a part of the title.
+    Word,
+}
+
+/// A single token of a todo.txt line.
+/// The text of the token is stored exactly as it was found in the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// The kind of the token.
+    pub kind: TokenKind,
+    /// The text of the token.
+    pub text: String,
+}
+
+impl Token {
+    /// Creates a new token of the given kind.
+    pub fn new(kind: TokenKind, text: &str) -> Self {
+        Token {
+            kind,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// Splits a todo.txt line into tokens using the default `ParseMode`.
+/// The header of the line (completion marker, priority and dates) is classified first, the
+/// rest of the words are classified as projects, contexts, tags or title words.
+pub fn tokenize(line: &str) -> Vec<Token> {
+    tokenize_with(line, ParseMode::default())
+}
+
+/// Splits a todo.txt line into tokens, classifying the header according to the mode.
+pub fn tokenize_with(line: &str, mode: ParseMode) -> Vec<Token> {
+    Lexer::new(line, mode)
+        .map(|l| Token::new(l.kind, l.text))
+        .collect()
+}
+
+/// Renders the tokens back into a todo.txt line.
+pub fn render(tokens: &[Token]) -> String {
+    tokens.iter().map(|t| t.text.as_str()).collect()
+}
+
+/// A token borrowed from the line it was lexed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexeme<'a> {
+    /// The kind of the token.
+    pub kind: TokenKind,
+    /// The text of the token.
+    pub text: &'a str,
+    /// The byte range of the token in the line.
+    pub span: std::ops::Range<usize>,
+}
+
+/// A single pass lexer over a todo.txt line.
+/// The lexer doesn't allocate, every `Lexeme` it yields borrows from the line. It is the
+/// building block of all of the parsing in the library, from `Todo::parse` to the
+/// `parse_*` functions and the `view::TodoView` struct.
+///
+/// ```rust
+/// use libdonow::parser::ParseMode;
+/// use libdonow::token::{Lexer, TokenKind};
+///
+/// let projects = Lexer::new("Call mom +family @phone +home", ParseMode::Strict)
+///     .filter(|l| l.kind == TokenKind::Project)
+///     .map(|l| l.text)
+///     .collect::<Vec<_>>();
+/// assert_eq!(projects, vec!["+family", "+home"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Lexer<'a> {
+    line: &'a str,
+    pos: usize,
+    mode: ParseMode,
+    stage: Stage,
+    completed: bool,
+    priority: bool,
+    dates: usize,
+}
+
+/// The part of the header the lexer expects next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Stage {
+    Marker,
+    Priority,
+    CompletionDate,
+    CreationDate,
+    Body,
+}
+
+impl<'a> Lexer<'a> {
+    /// Creates a lexer over the line.
+    pub fn new(line: &'a str, mode: ParseMode) -> Self {
+        Lexer {
+            line,
+            pos: 0,
+            mode,
+            stage: Stage::Marker,
+            completed: false,
+            priority: false,
+            dates: 0,
+        }
+    }
+
+    /// Classifies the header the way the todo.txt format describes it.
+    /// A completed todo starts with `x ` followed by an optional priority, the completion date
+    /// and the creation date. An open todo starts with an optional priority followed by the
+    /// creation date. Anything else is part of the body.
+    fn strict(&mut self, text: &str) -> TokenKind {
+        while self.stage < Stage::Body {
+            let stage = self.stage;
+            self.stage = match stage {
+                Stage::Marker => Stage::Priority,
+                Stage::Priority => Stage::CompletionDate,
+                Stage::CompletionDate => Stage::CreationDate,
+                _ => Stage::Body,
+            };
+            match stage {
+                Stage::Marker if text == "x" => {
+                    self.completed = true;
+                    return TokenKind::Completed;
+                }
+                Stage::Priority if is_priority(text) => return TokenKind::Priority,
+                Stage::CompletionDate if self.completed && is_date(text) => {
+                    return TokenKind::CompletionDate
+                }
+                Stage::CreationDate if is_date(text) => return TokenKind::CreationDate,
+                _ => {}
+            }
+        }
+        body(text)
+    }
+
+    /// Classifies the header the way libdonow always has.
+    /// Any line starting with an `x` is completed, the first `(A)` anywhere in the line is the
+    /// priority and the first two dates anywhere in the line are the creation and completion
+    /// dates.
+    fn lenient(&mut self, text: &str) -> TokenKind {
+        if !self.priority && is_priority(text) {
+            self.priority = true;
+            TokenKind::Priority
+        } else if self.dates < 2 && is_date(text) {
+            self.dates += 1;
+            if self.dates == 1 {
+                TokenKind::CreationDate
+            } else {
+                TokenKind::CompletionDate
+            }
+        } else {
+            body(text)
+        }
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Lexeme<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.pos;
+        let rest = &self.line[start..];
+        let first = rest.chars().next()?;
+        let space = first.is_whitespace();
+        let len = rest
+            .find(|c: char| c.is_whitespace() != space)
+            .unwrap_or(rest.len());
+        let mut end = start + len;
+
+        let kind = if space {
+            TokenKind::Whitespace
+        } else if self.mode == ParseMode::Lenient && start == 0 && first == 'x' {
+            // a leading x marks the todo as done, even when it is glued to the next word
+            end = start + 1;
+            TokenKind::Completed
+        } else {
+            let text = &self.line[start..end];
+            match self.mode {
+                ParseMode::Strict => self.strict(text),
+                ParseMode::Lenient => self.lenient(text),
+            }
+        };
+
+        self.pos = end;
+        Some(Lexeme {
+            kind,
+            text: &self.line[start..end],
+            span: start..end,
+        })
+    }
+}
+
+/// Classifies a word of the body of the line.
+fn body(text: &str) -> TokenKind {
+    if is_prefixed(text, '+') {
+        TokenKind::Project
+    } else if is_prefixed(text, '@') {
+        TokenKind::Context
+    } else if is_tag(text) {
+        TokenKind::Tag
+    } else {
+        TokenKind::Word
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+pub(crate) fn is_word(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(is_word_char)
+}
+
+/// A priority, `(A)` to `(Z)`.
+/// The todo.txt format only knows uppercase priorities, so `(a)` is a part of the title.
+fn is_priority(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 3 && b[0] == b'(' && b[1].is_ascii_uppercase() && b[2] == b')'
+}
+
+/// Checks if the word has the shape of a `YYYY-MM-DD` date.
+/// Whether the date exists is checked when it is parsed, so that a typo like `2024-13-45` is
+/// reported as an invalid date instead of becoming a part of the title.
+pub(crate) fn is_date(s: &str) -> bool {
+    s.len() == 10
+        && s.bytes().enumerate().all(|(i, b)| match i {
+            4 | 7 => b == b'-',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+/// A `+project` or `@context`.
+/// The name is made of words joined by single `.`, `/` or `-` separators, so hierarchical
+/// names like `+work.backend` are a single project. A separator at either end isn't a part of
+/// a name, which keeps `+project.` at the end of a sentence in the title.
+fn is_prefixed(s: &str, prefix: char) -> bool {
+    s.strip_prefix(prefix).is_some_and(is_name)
+}
+
+pub(crate) fn is_name(s: &str) -> bool {
+    s.split(NAME_SEPARATORS).all(is_word)
+}
+
+/// The characters that can join the words of a project or context name.
+pub const NAME_SEPARATORS: [char; 3] = ['.', '/', '-'];
+
+/// A tag is a whole word in the format `key:value`.
+/// Following the todo.txt format, neither side may contain another colon. On top of that, the
+/// key can't be only digits, so a time like `10:30` stays in the title, and the value can't
+/// start with `//`, so a url like `https://example.com` stays in the title as well.
+fn is_tag(s: &str) -> bool {
+    match s.split_once(':') {
+        Some((k, v)) => {
+            is_word(k)
+                && !k.bytes().all(|b| b.is_ascii_digit())
+                && !v.is_empty()
+                && !v.contains(':')
+                && !v.starts_with("//")
+        }
+        None => false,
+    }
+}
+
+/// Returns whether `key:value` is read back as a tag with that key and value.
+pub(crate) fn is_tag_of(key: &str, value: &str) -> bool {
+    !value.contains(char::is_whitespace) && is_tag(&format!("{}:{}", key, value))
+}
+
+/// Returns the index of the first token of the given kind.
+pub(crate) fn find(tokens: &[Token], kind: TokenKind) -> Option<usize> {
+    tokens.iter().position(|t| t.kind == kind)
+}
+
+/// Removes the token at the index along with one of the whitespace tokens around it, so that
+/// the rest of the line keeps its spacing.
+pub(crate) fn remove(tokens: &mut Vec<Token>, i: usize) {
+    tokens.remove(i);
+    if tokens
+        .get(i)
+        .is_some_and(|t| t.kind == TokenKind::Whitespace)
+    {
+        tokens.remove(i);
+    } else if i > 0 && tokens[i - 1].kind == TokenKind::Whitespace {
+        tokens.remove(i - 1);
+    }
+}
+
+/// Inserts a token at the index and separates it from its neighbours with a single space.
+/// Returns the index the token ended up at.
+pub(crate) fn insert(tokens: &mut Vec<Token>, i: usize, token: Token) -> usize {
+    tokens.insert(i, token);
+    if tokens
+        .get(i + 1)
+        .is_some_and(|t| t.kind != TokenKind::Whitespace)
+    {
+        tokens.insert(i + 1, Token::new(TokenKind::Whitespace, " "));
+    }
+    if i > 0 && tokens[i - 1].kind != TokenKind::Whitespace {
+        tokens.insert(i, Token::new(TokenKind::Whitespace, " "));
+        return i + 1;
+    }
+    i
+}
+
+#[cfg(test)]
+#[test]
+fn test_tokenize_round_trip() {
+    let line = "  x (A)  2024-08-15 2024-09-20 Hello   World +hello @wow due:123\t";
+    assert_eq!(render(&tokenize(line)), line);
+}
+
+#[test]
+fn test_tokenize_kinds() {
+    let tokens = tokenize("x (A) 2024-08-15 Hello +hello @wow due:123");
+    let kinds = tokens
+        .iter()
+        .filter(|t| t.kind != TokenKind::Whitespace)
+        .map(|t| t.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Completed,
+            TokenKind::Priority,
+            TokenKind::CompletionDate,
+            TokenKind::Word,
+            TokenKind::Project,
+            TokenKind::Context,
+            TokenKind::Tag
+        ]
+    );
+}
+
+#[test]
+fn test_tokenize_strict() {
+    let kind = |line: &str, i: usize| tokenize_with(line, ParseMode::Strict)[i].kind;
+    assert_eq!(kind("xylophone practice", 0), TokenKind::Word);
+    assert_eq!(
+        kind("x 2024-09-20 2024-08-15 Done", 2),
+        TokenKind::CompletionDate
+    );
+    assert_eq!(
+        kind("x 2024-09-20 2024-08-15 Done", 4),
+        TokenKind::CreationDate
+    );
+    assert_eq!(kind("2024-08-15 Done", 0), TokenKind::CreationDate);
+    assert_eq!(kind("Meet (A) team", 2), TokenKind::Word);
+    assert_eq!(kind("(hello) team", 0), TokenKind::Word);
+    assert_eq!(kind("(a) team", 0), TokenKind::Word);
+    assert_eq!(kind("Meet 2024-08-15", 2), TokenKind::Word);
+}
+
+#[test]
+fn test_tokenize_lenient() {
+    let kind = |line: &str, i: usize| tokenize_with(line, ParseMode::Lenient)[i].kind;
+    assert_eq!(kind("xylophone practice", 0), TokenKind::Completed);
+    assert_eq!(kind("Meet (A) team", 2), TokenKind::Priority);
+    assert_eq!(
+        kind("x 2024-09-20 2024-08-15 Done", 2),
+        TokenKind::CreationDate
+    );
+}
+
+#[test]
+fn test_lexer_spans() {
+    let line = "(A) Call  +mom";
+    for l in Lexer::new(line, ParseMode::Strict) {
+        assert_eq!(&line[l.span.clone()], l.text);
+    }
+    assert_eq!(Lexer::new(line, ParseMode::Strict).count(), 5);
+}
+
+#[test]
+fn test_remove_insert() {
+    let mut tokens = tokenize("Hello big World");
+    remove(&mut tokens, 2);
+    assert_eq!(render(&tokens), "Hello World");
+    insert(&mut tokens, 0, Token::new(TokenKind::Priority, "(B)"));
+    assert_eq!(render(&tokens), "(B) Hello World");
+    let len = tokens.len();
+    insert(&mut tokens, len, Token::new(TokenKind::Project, "+p"));
+    assert_eq!(render(&tokens), "(B) Hello World +p");
+}
+
+/// A line along with the projects, contexts, tags and title it should parse into.
+#[cfg(test)]
+type Regression<'a> = (
+    &'a str,
+    &'a [&'a str],
+    &'a [&'a str],
+    &'a [(&'a str, &'a str)],
+    &'a str,
+);
+
+/// Tricky real world lines. Add to this list whenever a line is tokenized the wrong way.
+#[cfg(test)]
+const REGRESSIONS: &[Regression] = &[
+    (
+        "Read https://example.com/a?b=c +reading",
+        &["reading"],
+        &[],
+        &[],
+        "Read https://example.com/a?b=c",
+    ),
+    (
+        "Email me@corp.com about the offsite @work",
+        &[],
+        &["work"],
+        &[],
+        "Email me@corp.com about the offsite",
+    ),
+    (
+        "Check a+b=c in the proof",
+        &[],
+        &[],
+        &[],
+        "Check a+b=c in the proof",
+    ),
+    (
+        "Standup at 10:30 @office",
+        &[],
+        &["office"],
+        &[],
+        "Standup at 10:30",
+    ),
+    (
+        "Open ftp://files.local and http://x.y:8080/z due:2024-09-01",
+        &[],
+        &[],
+        &[("due", "2024-09-01")],
+        "Open ftp://files.local and http://x.y:8080/z",
+    ),
+    ("Note: buy milk", &[], &[], &[], "Note: buy milk"),
+    (
+        "Fix login +work.backend.auth @home/office +side-project",
+        &["work.backend.auth", "side-project"],
+        &["home/office"],
+        &[],
+        "Fix login",
+    ),
+    (
+        "Wrap up +report. Then rest +.hidden",
+        &[],
+        &[],
+        &[],
+        "Wrap up +report. Then rest +.hidden",
+    ),
+    (
+        "Learn C++ and C# +skills",
+        &["skills"],
+        &[],
+        &[],
+        "Learn C++ and C#",
+    ),
+    (
+        "Ping @ 5 or + 2 more people",
+        &[],
+        &[],
+        &[],
+        "Ping @ 5 or + 2 more people",
+    ),
+    (
+        "Ask bob@example.org re: invoice #123 +billing",
+        &["billing"],
+        &[],
+        &[],
+        "Ask bob@example.org re: invoice #123",
+    ),
+    (
+        "Emoji :smile: and ratio 16:9 key:a:b",
+        &[],
+        &[],
+        &[],
+        "Emoji :smile: and ratio 16:9 key:a:b",
+    ),
+    (
+        "Deploy dep:3 dep:7 +ops @laptop",
+        &["ops"],
+        &["laptop"],
+        &[("dep", "3"), ("dep", "7")],
+        "Deploy",
+    ),
+];
+
+#[test]
+fn test_regressions() {
+    for (line, projects, contexts, tags, title) in REGRESSIONS {
+        let t = crate::parser::Todo::parse(line).unwrap();
+        assert_eq!(&t.projects, projects, "projects of {:?}", line);
+        assert_eq!(&t.contexts, contexts, "contexts of {:?}", line);
+        assert_eq!(
+            &t.others.iter().collect::<Vec<_>>(),
+            tags,
+            "tags of {:?}",
+            line
+        );
+        assert_eq!(&t.title, title, "title of {:?}", line);
+        assert_eq!(
+            t.parse_title().unwrap(),
+            *title,
+            "parse_title of {:?}",
+            line
+        );
+        assert_eq!(t.to_string(), *line);
+    }
+}
@@ -0,0 +1,134 @@
+//! # Tree
+//!
+//! Support for hierarchical project and context names like `+work.backend.auth`.
+//! A name is split into levels on a separator, `.` by default, so `work.backend.auth` is a
+//! descendant of `work.backend` and of `work`. The separator is one of the
+//! `token::NAME_SEPARATORS` and is set with `TodoFile::set_separator`.
+//!
+//! ```rust
+//! use libdonow::tree::{build, is_within};
+//!
+//! let tree = build(["work.backend.auth", "work.frontend", "home"], '.');
+//! assert_eq!(tree.len(), 2);
+//! assert_eq!(tree[1].children[0].path, "work.backend");
+//! assert!(is_within("work.backend.auth", "work", '.'));
+//! assert!(!is_within("workshop", "work", '.'));
+//! ```
+
+use std::fmt::Display;
+
+/// A level of a hierarchical name along with the levels below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// The name of the level, like `backend`.
+    pub name: String,
+    /// The full name up to and including the level, like `work.backend`.
+    pub path: String,
+    /// The levels below this one, sorted by name.
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(name: &str, path: &str) -> Self {
+        Node {
+            name: name.to_string(),
+            path: path.to_string(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Returns the node and all of its descendants, depth first.
+    pub fn iter(&self) -> impl Iterator<Item = &Node> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.children.iter().rev());
+            Some(node)
+        })
+    }
+
+    fn write(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.name, indent = depth * 2)?;
+        for child in &self.children {
+            child.write(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Node {
+    /// Writes the node and its descendants one per line, indenting each level by two spaces.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// Builds the trees of the given names.
+/// Every level of a name becomes a node, even if the level isn't used as a name on its own.
+/// The roots and the children of every node are sorted by name.
+pub fn build<'a>(names: impl IntoIterator<Item = &'a str>, separator: char) -> Vec<Node> {
+    let mut roots: Vec<Node> = Vec::new();
+    for name in names {
+        let mut level = &mut roots;
+        let mut end = 0;
+        for part in name.split(separator) {
+            end += part.len();
+            let i = match level.binary_search_by(|n| n.name.as_str().cmp(part)) {
+                Ok(i) => i,
+                Err(i) => {
+                    level.insert(i, Node::new(part, &name[..end]));
+                    i
+                }
+            };
+            level = &mut level[i].children;
+            end += separator.len_utf8();
+        }
+    }
+
+    roots
+}
+
+/// Returns whether the name is the ancestor or one of its descendants.
+pub fn is_within(name: &str, ancestor: &str, separator: char) -> bool {
+    name.strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(separator))
+}
+
+#[cfg(test)]
+#[test]
+fn test_build() {
+    let tree = build(["work.frontend", "work.backend.auth", "work", "home"], '.');
+    let paths = tree
+        .iter()
+        .flat_map(Node::iter)
+        .map(|n| n.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec![
+            "home",
+            "work",
+            "work.backend",
+            "work.backend.auth",
+            "work.frontend"
+        ]
+    );
+    assert_eq!(
+        tree[1].to_string(),
+        "work\n  backend\n    auth\n  frontend\n"
+    );
+
+    let tree = build(["work/q3", "work.q3"], '/');
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree[0].children[0].path, "work/q3");
+}
+
+#[test]
+fn test_is_within() {
+    assert!(is_within("work", "work", '.'));
+    assert!(is_within("work.backend", "work", '.'));
+    assert!(!is_within("work.backend", "work", '/'));
+    assert!(!is_within("workshop", "work", '.'));
+    assert!(!is_within("work", "work.backend", '.'));
+}
@@ -0,0 +1,165 @@
+//! # Todo View
+//!
+//! A borrowed, read only view over a single todo.txt line.
+//! Unlike `parser::Todo`, a `TodoView` doesn't own or parse anything up front. Every accessor
+//! runs the `token::Lexer` over the line and returns slices of it, so scanning and filtering a
+//! big file doesn't allocate a `Todo` for each line.
+//!
+//! ```rust
+//! use libdonow::view::TodoView;
+//!
+//! let v = TodoView::new("(A) 2024-08-15 Call mom +family @phone due:2024-09-01");
+//! assert_eq!(v.priority(), Some("A"));
+//! assert_eq!(v.projects().collect::<Vec<_>>(), vec!["family"]);
+//! assert_eq!(v.tag("due"), Some("2024-09-01"));
+//! ```
+
+use crate::{
+    parser::{parse_date, ParseMode, Todo, TodoErr},
+    token::{Lexeme, Lexer, TokenKind},
+};
+
+/// A borrowed view over a todo.txt line.
+/// The view is `Copy` and cheap to create, the line is only lexed when an accessor is called.
+#[derive(Debug, Clone, Copy)]
+pub struct TodoView<'a> {
+    line: &'a str,
+    mode: ParseMode,
+}
+
+impl<'a> TodoView<'a> {
+    /// Creates a view over the line using the default `ParseMode`.
+    pub fn new(line: &'a str) -> Self {
+        TodoView::with_mode(line, ParseMode::default())
+    }
+
+    /// Creates a view over the line using the given `ParseMode`.
+    pub fn with_mode(line: &'a str, mode: ParseMode) -> Self {
+        TodoView { line, mode }
+    }
+
+    /// Returns the line the view is over.
+    pub fn line(&self) -> &'a str {
+        self.line
+    }
+
+    /// Returns a lexer over the line.
+    pub fn lexemes(&self) -> Lexer<'a> {
+        Lexer::new(self.line, self.mode)
+    }
+
+    fn of_kind(&self, kind: TokenKind) -> impl Iterator<Item = &'a str> {
+        self.lexemes()
+            .filter(move |l| l.kind == kind)
+            .map(|l: Lexeme<'a>| l.text)
+    }
+
+    fn first(&self, kind: TokenKind) -> Option<&'a str> {
+        self.of_kind(kind).next()
+    }
+
+    /// Returns whether the todo item is completed.
+    pub fn is_completed(&self) -> bool {
+        self.first(TokenKind::Completed).is_some()
+    }
+
+    /// Returns the priority of the todo item without the parentheses.
+    pub fn priority(&self) -> Option<&'a str> {
+        self.first(TokenKind::Priority).map(|p| &p[1..p.len() - 1])
+    }
+
+    /// Returns the creation date of the todo item.
+    pub fn creation(&self) -> Option<chrono::NaiveDate> {
+        self.first(TokenKind::CreationDate).and_then(parse_date)
+    }
+
+    /// Returns the completion date of the todo item.
+    pub fn completion(&self) -> Option<chrono::NaiveDate> {
+        self.first(TokenKind::CompletionDate).and_then(parse_date)
+    }
+
+    /// Returns the projects of the todo item without the leading `+`.
+    pub fn projects(&self) -> impl Iterator<Item = &'a str> {
+        self.of_kind(TokenKind::Project).map(|p| &p[1..])
+    }
+
+    /// Returns the contexts of the todo item without the leading `@`.
+    pub fn contexts(&self) -> impl Iterator<Item = &'a str> {
+        self.of_kind(TokenKind::Context).map(|c| &c[1..])
+    }
+
+    /// Returns the `key:value` tags of the todo item in the order they appear.
+    pub fn tags(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.of_kind(TokenKind::Tag)
+            .filter_map(|t| t.split_once(':'))
+    }
+
+    /// Returns the value of the first tag with the given key.
+    pub fn tag(&self, key: &str) -> Option<&'a str> {
+        self.tags().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Returns the due date of the todo item from the `due:` tag.
+    pub fn due(&self) -> Option<chrono::NaiveDate> {
+        self.date_tag("due")
+    }
+
+    /// Returns the threshold date of the todo item from the `t:` tag.
+    /// The todo item shouldn't be shown before this date.
+    pub fn threshold(&self) -> Option<chrono::NaiveDate> {
+        self.date_tag("t")
+    }
+
+    fn date_tag(&self, key: &str) -> Option<chrono::NaiveDate> {
+        self.tags()
+            .filter(|(k, _)| *k == key)
+            .find_map(|(_, v)| parse_date(v))
+    }
+
+    /// Returns the words making up the title of the todo item.
+    pub fn title_words(&self) -> impl Iterator<Item = &'a str> {
+        self.of_kind(TokenKind::Word)
+    }
+
+    /// Experimental: Returns the `#hashtags` of the todo item including the `#`.
+    pub fn hashtags(&self) -> impl Iterator<Item = &'a str> {
+        self.title_words()
+            .filter(|w| w.strip_prefix('#').is_some_and(crate::token::is_word))
+    }
+
+    /// Parses the line into an owned `Todo`.
+    pub fn to_todo(&self) -> Result<Todo, TodoErr> {
+        Todo::parse_with(self.line, self.mode)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_view() {
+    let v = TodoView::new("x (A) 2024-09-20 2024-08-15 Call mom #family +a +b @c k:1 k:2");
+    assert!(v.is_completed());
+    assert_eq!(v.priority(), Some("A"));
+    assert_eq!(v.completion(), chrono::NaiveDate::from_ymd_opt(2024, 9, 20));
+    assert_eq!(v.creation(), chrono::NaiveDate::from_ymd_opt(2024, 8, 15));
+    assert_eq!(v.projects().collect::<Vec<_>>(), vec!["a", "b"]);
+    assert_eq!(v.contexts().collect::<Vec<_>>(), vec!["c"]);
+    assert_eq!(v.tags().collect::<Vec<_>>(), vec![("k", "1"), ("k", "2")]);
+    assert_eq!(
+        v.title_words().collect::<Vec<_>>(),
+        vec!["Call", "mom", "#family"]
+    );
+    assert_eq!(v.hashtags().collect::<Vec<_>>(), vec!["#family"]);
+    assert_eq!(v.threshold(), None);
+    assert_eq!(
+        TodoView::new("Later t:2024-10-01").threshold(),
+        chrono::NaiveDate::from_ymd_opt(2024, 10, 1)
+    );
+}
+
+#[test]
+fn test_view_to_todo() {
+    let v = TodoView::new("(B) Call mom +family");
+    let t = v.to_todo().unwrap();
+    assert_eq!(t.title, "Call mom");
+    assert_eq!(t.projects, vec!["family"]);
+}
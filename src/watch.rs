@@ -0,0 +1,176 @@
+//! # Watch
+//!
+//! Watching a todo.txt file for changes made by other programs. Needs the `watch` feature.
+//!
+//! A `Watcher` listens for changes to the file through the file system notifications of the
+//! platform. `poll` and `wait` reload the `TodoFile` with `TodoFile::reload` when the file
+//! changed, and hand the `TodoEvent`s to the caller and to every subscriber.
+//!
+//! ```rust,no_run
+//! use libdonow::file::TodoFile;
+//!
+//! let mut file = TodoFile::new("todo.txt");
+//! let mut watcher = file.watch().expect("Failed to watch todo.txt");
+//! let events = watcher.subscribe();
+//! std::thread::spawn(move || {
+//!     for event in events {
+//!         println!("{:?}", event);
+//!     }
+//! });
+//!
+//! loop {
+//!     watcher
+//!         .wait(&mut file, std::time::Duration::from_secs(1))
+//!         .expect("Failed to reload todo.txt");
+//! }
+//! ```
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::Duration,
+};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::{event::TodoEvent, file::TodoFile};
+
+/// Watches a todo.txt file for changes.
+/// The directory of the file is watched rather than the file itself, so that the file being
+/// replaced, like `TodoFile::save` does, is noticed as well.
+pub struct Watcher {
+    // kept alive for as long as the watcher is
+    _watcher: RecommendedWatcher,
+    changes: Receiver<()>,
+    subscribers: Vec<Sender<TodoEvent>>,
+}
+
+impl Watcher {
+    /// Starts watching the file at the path.
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let name = path.file_name().map(PathBuf::from).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "the path has no file name")
+        })?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let (tx, changes) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |e: notify::Result<notify::Event>| {
+            let ours = match e {
+                Ok(e) => e
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == Some(name.as_os_str())),
+                // let the next reload find out what happened
+                Err(_) => true,
+            };
+            if ours {
+                let _ = tx.send(());
+            }
+        })
+        .map_err(io::Error::other)?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+
+        Ok(Watcher {
+            _watcher: watcher,
+            changes,
+            subscribers: Vec::new(),
+        })
+    }
+
+    /// Returns a receiver that gets every event the watcher reloads from now on.
+    /// A subscriber that is dropped is removed on the next event.
+    pub fn subscribe(&mut self) -> Receiver<TodoEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Reloads the file if it changed since the last call, without blocking.
+    /// Returns the events of the reload, which are also sent to the subscribers.
+    pub fn poll(&mut self, file: &mut TodoFile) -> io::Result<Vec<TodoEvent>> {
+        if self.changes.try_iter().count() == 0 {
+            return Ok(Vec::new());
+        }
+
+        self.reload(file)
+    }
+
+    /// Waits up to the timeout for the file to change and reloads it.
+    /// Returns the events of the reload, which are also sent to the subscribers. The events
+    /// are empty if the file didn't change in time.
+    pub fn wait(&mut self, file: &mut TodoFile, timeout: Duration) -> io::Result<Vec<TodoEvent>> {
+        match self.changes.recv_timeout(timeout) {
+            Ok(()) => {
+                // editors tend to write a file in more than one step
+                std::thread::sleep(Duration::from_millis(20));
+                self.changes.try_iter().count();
+                self.reload(file)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(Vec::new()),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the file watcher stopped",
+            )),
+        }
+    }
+
+    fn reload(&mut self, file: &mut TodoFile) -> io::Result<Vec<TodoEvent>> {
+        let events = file.reload()?;
+        self.subscribers
+            .retain(|s| events.iter().all(|e| s.send(e.clone()).is_ok()));
+
+        Ok(events)
+    }
+}
+
+impl TodoFile {
+    /// Starts watching the file at `path` for changes. See the `watch` module.
+    pub fn watch(&self) -> io::Result<Watcher> {
+        Watcher::new(&self.path)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_watch() {
+    let dir = crate::file::TempDir::new("watch");
+    let path = dir.join("todo.txt");
+    std::fs::write(&path, "Call mom\n").unwrap();
+
+    let mut t = TodoFile::from_path(&path).unwrap();
+    let mut watcher = t.watch().unwrap();
+    let events = watcher.subscribe();
+    assert!(watcher.poll(&mut t).unwrap().is_empty());
+
+    std::fs::write(dir.join("other.txt"), "Not watched\n").unwrap();
+    std::fs::write(&path, "Call mom\nPay rent\n").unwrap();
+    let mut reloaded = Vec::new();
+    for _ in 0..50 {
+        reloaded.extend(watcher.wait(&mut t, Duration::from_millis(100)).unwrap());
+        if !reloaded.is_empty() {
+            break;
+        }
+    }
+    assert!(matches!(
+        reloaded.as_slice(),
+        [TodoEvent::Added { index: 1, todo }] if todo.title == "Pay rent"
+    ));
+    assert!(matches!(
+        events.try_recv(),
+        Ok(TodoEvent::Added { index: 1, .. })
+    ));
+
+    // our own saves don't show up as changes
+    t.add(crate::parser::Todo::parse("Water plants").unwrap());
+    t.save().unwrap();
+    assert!(watcher
+        .wait(&mut t, Duration::from_millis(200))
+        .unwrap()
+        .is_empty());
+}